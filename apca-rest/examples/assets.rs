@@ -1,6 +1,6 @@
 use apca_rest::{
     assets::{GetAsset, GetAssets},
-    paper_client,
+    paper_client, Identifier,
 };
 use std::env::var;
 
@@ -11,10 +11,17 @@ async fn main() {
     let client = paper_client(key, secret);
     let res = client.send(&GetAssets::new()).await.unwrap();
     println!("{:#?}", res);
-    let res = client.send(&GetAsset::new("AAPL")).await.unwrap();
+    let res = client
+        .send(&GetAsset::new("AAPL".parse::<Identifier>().unwrap()))
+        .await
+        .unwrap();
     println!("{:#?}", res);
     let res = client
-        .send(&GetAsset::new("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415"))
+        .send(&GetAsset::new(
+            "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415"
+                .parse::<Identifier>()
+                .unwrap(),
+        ))
         .await
         .unwrap();
     println!("{:#?}", res);
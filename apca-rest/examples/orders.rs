@@ -13,7 +13,8 @@ async fn main() {
     // WARNING: This example WILL issue an order to your paper account if run. The order sent is
     // deliberately sent with a very high limit price in the hopes that it will not be executed and
     // so can be cancelled right afterwards.
-    let req = SubmitOrder::new("AAPL").order_type(OrderType::limit(Decimal::new(10000, 0)));
+    let req = SubmitOrder::new("AAPL".parse().unwrap())
+        .order_type(OrderType::limit(Decimal::new(10000, 0)));
     let res = client.send(&req).await.unwrap();
     println!("{:#?}", res);
 
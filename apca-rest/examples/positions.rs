@@ -1,6 +1,7 @@
 use apca_rest::{
     paper_client,
     positions::{GetPosition, GetPositions},
+    Symbol,
 };
 use std::env::var;
 
@@ -11,6 +12,9 @@ async fn main() {
     let client = paper_client(key, secret);
     let res = client.send(&GetPositions).await.ok();
     println!("{:#?}", res);
-    let res = client.send(&GetPosition::new("AAPL")).await.ok();
+    let res = client
+        .send(&GetPosition::new("AAPL".parse::<Symbol>().unwrap()))
+        .await
+        .ok();
     println!("{:#?}", res);
 }
@@ -1,14 +1,17 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use uuid::Uuid;
 use vila::Request;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
 /// The following are the possible account status values. Most likely, the account status is `Active`
-/// unless there is any problem. The account status may get in `AccountUpdated` when personal
+/// unless there is any problem. The account status may get in `AccountUpdate` when personal
 /// information is being updated from the dashboard, in which case you may not be allowed trading
 /// for a short period of time until the change is approved.
 pub enum AccountStatus {
@@ -24,11 +27,43 @@ pub enum AccountStatus {
     ApprovalPending,
     /// The account is active for trading.
     Active,
-    /// The account is active for trading.
+    /// The account application has been rejected.
     Rejected,
+    /// A status not known to this crate.
+    Unknown(String),
+}
+
+impl From<String> for AccountStatus {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "ONBOARDING" => AccountStatus::Onboarding,
+            "SUBMISSION_FAILED" => AccountStatus::SubmissionFailed,
+            "SUBMITTED" => AccountStatus::Submitted,
+            "ACCOUNT_UPDATED" | "ACCOUNT_UPDATE" => AccountStatus::AccountUpdate,
+            "APPROVAL_PENDING" => AccountStatus::ApprovalPending,
+            "ACTIVE" => AccountStatus::Active,
+            "REJECTED" => AccountStatus::Rejected,
+            _ => AccountStatus::Unknown(s),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+impl From<AccountStatus> for String {
+    fn from(status: AccountStatus) -> Self {
+        match status {
+            AccountStatus::Onboarding => "ONBOARDING".into(),
+            AccountStatus::SubmissionFailed => "SUBMISSION_FAILED".into(),
+            AccountStatus::Submitted => "SUBMITTED".into(),
+            AccountStatus::AccountUpdate => "ACCOUNT_UPDATED".into(),
+            AccountStatus::ApprovalPending => "APPROVAL_PENDING".into(),
+            AccountStatus::Active => "ACTIVE".into(),
+            AccountStatus::Rejected => "REJECTED".into(),
+            AccountStatus::Unknown(s) => s,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 /// Account object
 pub struct Account {
     /// Account ID.
@@ -90,6 +125,114 @@ pub struct Account {
     /// Your buying power under Regulation T (your excess equity - equity minus margin value -
     /// times your margin multiplier)
     pub regt_buying_power: Decimal,
+    #[serde(default)]
+    /// The status of the account for crypto trading
+    pub crypto_status: Option<AccountStatus>,
+    #[serde(default)]
+    /// Fees accrued in this month
+    pub accrued_fees: Option<Decimal>,
+    #[serde(default)]
+    /// Cash pending transfer in
+    pub pending_transfer_in: Option<Decimal>,
+    #[serde(default)]
+    /// Cash pending transfer out
+    pub pending_transfer_out: Option<Decimal>,
+    #[serde(default)]
+    /// Buying power for assets that are not marginable, such as crypto
+    pub non_marginable_buying_power: Option<Decimal>,
+    #[serde(default)]
+    /// Real-time MtM value of all positions held in the account
+    pub position_market_value: Option<Decimal>,
+    #[serde(default)]
+    /// Buying power available for options trading
+    pub options_buying_power: Option<Decimal>,
+    #[serde(default)]
+    /// The options trading level the account has been approved for
+    pub options_approved_level: Option<u8>,
+    #[serde(default)]
+    /// The date that the account balances were last updated
+    pub balance_asof: Option<NaiveDate>,
+    #[serde(flatten)]
+    /// Fields returned by Alpaca that are not otherwise part of this struct
+    pub extra: HashMap<String, Value>,
+}
+
+/// The number of day trades a non-PDT account can make in a rolling 5 trading-day window before
+/// being flagged as a pattern day trader.
+pub const MAX_NON_PDT_DAYTRADES: u32 = 3;
+
+/// The minimum equity a pattern day trader must maintain in order to day trade.
+pub const PDT_MINIMUM_EQUITY: Decimal = Decimal::from_parts(25000, 0, 0, false, 0);
+
+impl Account {
+    /// Gross leverage, calculated as the sum of the long and (absolute) short market value over
+    /// equity. Returns `None` if equity is zero.
+    pub fn gross_leverage(&self) -> Option<Decimal> {
+        ratio(
+            self.long_market_value + self.short_market_value.abs(),
+            self.equity,
+        )
+    }
+
+    /// Net leverage, calculated as the long market value minus the (absolute) short market value,
+    /// over equity. Returns `None` if equity is zero.
+    pub fn net_leverage(&self) -> Option<Decimal> {
+        ratio(
+            self.long_market_value - self.short_market_value.abs(),
+            self.equity,
+        )
+    }
+
+    /// The fraction of equity used by the initial margin requirement. Returns `None` if equity is
+    /// zero.
+    pub fn initial_margin_usage(&self) -> Option<Decimal> {
+        ratio(self.initial_margin, self.equity)
+    }
+
+    /// The fraction of equity used by the maintenance margin requirement. A value above 1 means
+    /// the account is in a margin call. Returns `None` if equity is zero.
+    pub fn maintenance_margin_usage(&self) -> Option<Decimal> {
+        ratio(self.maintenance_margin, self.equity)
+    }
+
+    /// Equity in excess of the maintenance margin requirement.
+    pub fn excess_liquidity(&self) -> Decimal {
+        self.equity - self.maintenance_margin
+    }
+
+    /// The number of day trades that can be made before the account is restricted. For accounts
+    /// that are not flagged as pattern day traders, this is the number of day trades left before
+    /// the account gets flagged. Accounts that are flagged can day trade without limit as long as
+    /// their equity is at least $25,000, in which case `None` is returned, and cannot day trade at
+    /// all otherwise.
+    pub fn daytrades_remaining(&self) -> Option<u32> {
+        if !self.pattern_day_trader {
+            Some(MAX_NON_PDT_DAYTRADES.saturating_sub(self.daytrade_count))
+        } else if self.equity >= PDT_MINIMUM_EQUITY {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Profit/loss in dollars since the previous trading day's close.
+    pub fn intraday_pl(&self) -> Decimal {
+        self.equity - self.last_equity
+    }
+
+    /// Profit/loss percent (by a factor of 1) since the previous trading day's close. Returns
+    /// `None` if the previous equity is zero.
+    pub fn intraday_plpc(&self) -> Option<Decimal> {
+        ratio(self.intraday_pl(), self.last_equity)
+    }
+}
+
+fn ratio(numerator: Decimal, denominator: Decimal) -> Option<Decimal> {
+    if denominator.is_zero() {
+        None
+    } else {
+        Some(numerator / denominator)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -123,6 +266,7 @@ impl Request for GetAccount {
 mod test {
     use super::*;
     use crate::client_with_url;
+    use crate::test_fixtures::ACCOUNT;
     use mockito::mock;
 
     #[tokio::test]
@@ -138,31 +282,161 @@ mod test {
         client.send(&GetAccount).await.unwrap();
     }
 
-    const ACCOUNT: &'static str = r#"{
-	    "account_blocked": false,
-		"account_number": "010203ABCD",
-		"buying_power": "262113.632",
-		"cash": "-23140.2",
-		"created_at": "2019-06-12T22:47:07.99658Z",
+    fn account(multiplier: i64, equity: i64, pattern_day_trader: bool) -> Account {
+        let mut account: Account = serde_json::from_str(ACCOUNT).unwrap();
+        account.multiplier = Decimal::new(multiplier, 0);
+        account.equity = Decimal::new(equity, 0);
+        account.pattern_day_trader = pattern_day_trader;
+        account
+    }
+
+    #[test]
+    fn cash_account_metrics() {
+        let mut account = account(1, 10000, false);
+        account.last_equity = Decimal::new(9800, 0);
+        account.long_market_value = Decimal::new(6000, 0);
+        account.short_market_value = Decimal::ZERO;
+        account.initial_margin = Decimal::ZERO;
+        account.maintenance_margin = Decimal::ZERO;
+        account.daytrade_count = 1;
+
+        assert_eq!(account.gross_leverage(), Some(Decimal::new(6, 1)));
+        assert_eq!(account.net_leverage(), Some(Decimal::new(6, 1)));
+        assert_eq!(account.initial_margin_usage(), Some(Decimal::ZERO));
+        assert_eq!(account.excess_liquidity(), Decimal::new(10000, 0));
+        assert_eq!(account.daytrades_remaining(), Some(2));
+        assert_eq!(account.intraday_pl(), Decimal::new(200, 0));
+        assert_eq!(
+            account.intraday_plpc().unwrap().round_dp(4),
+            Decimal::new(204, 4)
+        );
+    }
+
+    #[test]
+    fn reg_t_account_metrics() {
+        let mut account = account(2, 20000, false);
+        account.long_market_value = Decimal::new(30000, 0);
+        account.short_market_value = Decimal::new(-10000, 0);
+        account.initial_margin = Decimal::new(20000, 0);
+        account.maintenance_margin = Decimal::new(12000, 0);
+        account.daytrade_count = 4;
+
+        assert_eq!(account.gross_leverage(), Some(Decimal::new(2, 0)));
+        assert_eq!(account.net_leverage(), Some(Decimal::ONE));
+        assert_eq!(account.initial_margin_usage(), Some(Decimal::ONE));
+        assert_eq!(account.maintenance_margin_usage(), Some(Decimal::new(6, 1)));
+        assert_eq!(account.excess_liquidity(), Decimal::new(8000, 0));
+        assert_eq!(account.daytrades_remaining(), Some(0));
+
+        account.equity = Decimal::ZERO;
+        assert_eq!(account.gross_leverage(), None);
+    }
+
+    #[test]
+    fn pdt_account_metrics() {
+        let account = account(4, 103820, true);
+        assert_eq!(account.daytrades_remaining(), None);
+        assert_eq!(
+            account.gross_leverage().unwrap().round_dp(4),
+            Decimal::new(12229, 4)
+        );
+
+        let account = self::account(4, 24000, true);
+        assert_eq!(account.daytrades_remaining(), Some(0));
+    }
+
+    #[test]
+    fn deserialize_full_account() {
+        let account: Account = serde_json::from_str(FULL_ACCOUNT).unwrap();
+        assert_eq!(account.status, AccountStatus::Active);
+        assert_eq!(account.crypto_status, Some(AccountStatus::Active));
+        assert_eq!(account.accrued_fees, Some(Decimal::ZERO));
+        assert_eq!(account.pending_transfer_in, Some(Decimal::new(2500, 0)));
+        assert_eq!(account.pending_transfer_out, None);
+        assert_eq!(
+            account.non_marginable_buying_power,
+            Some(Decimal::new(995726, 1))
+        );
+        assert_eq!(account.position_market_value, Some(Decimal::ZERO));
+        assert_eq!(account.options_buying_power, Some(Decimal::new(995726, 1)));
+        assert_eq!(account.options_approved_level, Some(2));
+        assert_eq!(account.balance_asof, NaiveDate::from_ymd_opt(2024, 1, 12));
+        assert_eq!(account.extra["crypto_tier"], 1);
+        assert_eq!(account.extra["portfolio_value"], "99572.6");
+        assert!(!account.extra.contains_key("equity"));
+
+        let round_trip: Account =
+            serde_json::from_str(&serde_json::to_string(&account).unwrap()).unwrap();
+        assert_eq!(round_trip, account);
+    }
+
+    #[test]
+    fn deserialize_account_status() {
+        let account: Account = serde_json::from_str(ACCOUNT).unwrap();
+        assert_eq!(account.crypto_status, None);
+        assert!(account.extra.is_empty());
+
+        let statuses: Vec<AccountStatus> = serde_json::from_str(
+            r#"["ONBOARDING", "ACCOUNT_UPDATED", "REJECTED", "ACTION_REQUIRED"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            statuses,
+            vec![
+                AccountStatus::Onboarding,
+                AccountStatus::AccountUpdate,
+                AccountStatus::Rejected,
+                AccountStatus::Unknown("ACTION_REQUIRED".into()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&statuses).unwrap(),
+            r#"["ONBOARDING","ACCOUNT_UPDATED","REJECTED","ACTION_REQUIRED"]"#
+        );
+    }
+
+    const FULL_ACCOUNT: &'static str = r#"{
+		"id": "2f0e8a1c-5b4e-4f7a-9d55-2b0c1f3b9d8e",
+		"admin_configurations": {},
+		"user_configurations": null,
+		"account_number": "PA3GXRTXSMF3",
+		"status": "ACTIVE",
+		"crypto_status": "ACTIVE",
+		"options_approved_level": 2,
+		"options_trading_level": 2,
 		"currency": "USD",
-		"daytrade_count": 0,
-		"daytrading_buying_power": "262113.632",
-		"equity": "103820.56",
-		"id": "e6fe16f3-64a4-4921-8928-cadf02f92f98",
-		"initial_margin": "63480.38",
-		"last_equity": "103529.24",
-		"last_maintenance_margin": "38000.832",
-		"long_market_value": "126960.76",
-		"maintenance_margin": "38088.228",
-		"multiplier": "4",
+		"buying_power": "199145.2",
+		"regt_buying_power": "199145.2",
+		"daytrading_buying_power": "0",
+		"effective_buying_power": "199145.2",
+		"non_marginable_buying_power": "99572.6",
+		"options_buying_power": "99572.6",
+		"bod_dtbp": "0",
+		"cash": "99572.6",
+		"accrued_fees": "0",
+		"pending_transfer_in": "2500",
+		"portfolio_value": "99572.6",
 		"pattern_day_trader": false,
-		"regt_buying_power": "80680.36",
-		"short_market_value": "0",
+		"trading_blocked": false,
+		"transfers_blocked": false,
+		"account_blocked": false,
+		"created_at": "2023-09-27T18:42:10.573093Z",
+		"trade_suspended_by_user": false,
+		"multiplier": "2",
 		"shorting_enabled": true,
+		"equity": "99572.6",
+		"last_equity": "99572.6",
+		"long_market_value": "0",
+		"short_market_value": "0",
+		"position_market_value": "0",
+		"initial_margin": "0",
+		"maintenance_margin": "0",
+		"last_maintenance_margin": "0",
 		"sma": "0",
-		"status": "ACTIVE",
-		"trade_suspended_by_user": false,
-		"trading_blocked": false,
-		"transfers_blocked": false
+		"daytrade_count": 0,
+		"balance_asof": "2024-01-12",
+		"crypto_tier": 1,
+		"intraday_adjustments": "0",
+		"pending_reg_taf_fees": "0"
 	}"#;
 }
@@ -1,4 +1,3 @@
-use crate::utils::*;
 use crate::Sort;
 use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
@@ -13,7 +12,7 @@ use vila::pagination::{
 };
 use vila::{Request, RequestData};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 /// Whether a TradeActivity is a partial or full fill.
 pub enum FillType {
@@ -23,7 +22,7 @@ pub enum FillType {
     PartialFill,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 /// Whether a TradeActivity is a buy, sell or short-sell.
 pub enum Side {
     #[serde(rename = "buy")]
@@ -37,7 +36,7 @@ pub enum Side {
     SellShort,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 /// Activity
 pub enum Activity {
@@ -68,31 +67,299 @@ pub enum Activity {
         fill_type: FillType,
     },
     /// Activity type not involved in trades
-    NonTradeActivity {
-        /// See ActivityType for a list of possible values.
-        activity_type: ActivityType,
-        /// An ID for the activity, always in “::” format. Can be sent as page_token in requests to
-        /// facilitate the paging of results.
-        id: String,
-        /// The date on which the activity occurred or on which the transaction associated with the
-        /// activity settled.
-        date: NaiveDate,
-        /// The net amount of money (positive or negative) associated with the activity.
-        net_amount: Decimal,
-        /// The symbol of the security involved with the activity. Not present for all activity
-        /// types.
+    NonTradeActivity(NonTradeActivity),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "RawNonTradeActivity", into = "RawNonTradeActivity")]
+/// Activity not involved in trades, such as dividends, fees, transfers and corporate actions.
+pub struct NonTradeActivity {
+    /// See ActivityType for a list of possible values.
+    pub activity_type: ActivityType,
+    /// An ID for the activity, always in “::” format. Can be sent as page_token in requests to
+    /// facilitate the paging of results.
+    pub id: String,
+    /// The date on which the activity occurred or on which the transaction associated with the
+    /// activity settled.
+    pub date: NaiveDate,
+    /// The net amount of money (positive or negative) associated with the activity.
+    pub net_amount: Decimal,
+    /// A description of the activity. Not present for all activity types.
+    pub description: Option<String>,
+    /// The fields specific to the category of the activity.
+    pub details: ActivityDetails,
+}
+
+impl NonTradeActivity {
+    /// The symbol of the security involved with the activity, if any.
+    pub fn symbol(&self) -> Option<&str> {
+        match &self.details {
+            ActivityDetails::Dividend { symbol, .. }
+            | ActivityDetails::Split { symbol, .. }
+            | ActivityDetails::Spinoff { symbol, .. }
+            | ActivityDetails::Merger { symbol, .. } => Some(symbol),
+            ActivityDetails::Interest { symbol, .. }
+            | ActivityDetails::Fee { symbol, .. }
+            | ActivityDetails::Journal { symbol, .. }
+            | ActivityDetails::Transfer { symbol, .. }
+            | ActivityDetails::Other { symbol, .. } => symbol.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The fields specific to each category of non-trade activity. Activities that are missing the
+/// fields required by their category are reported as `Other`.
+pub enum ActivityDetails {
+    /// Dividends and their adjustments, other than dividend fees.
+    Dividend {
+        /// The symbol of the security paying the dividend.
+        symbol: String,
+        /// The number of shares that contributed to the payment.
+        qty: Option<Decimal>,
+        /// The average amount paid per share.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Interest on credit or margin balances, and its adjustments.
+    Interest {
+        /// The symbol of the security the interest relates to, if any.
         symbol: Option<String>,
-        #[serde(
-            deserialize_with = "from_str_optional",
-            serialize_with = "to_string_optional"
-        )]
-        /// For dividend activities, the number of shares that contributed to the payment. Not
-        /// present for other activity types.
-        qty: Option<i32>,
-        /// For dividend activities, the average amount paid per share. Not present for other
-        /// activity types.
+        /// The quantity the interest relates to, if any.
+        qty: Option<Decimal>,
+        /// The amount per share, if any.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Fees, including dividend fees, crypto fees and pass through charges and rebates.
+    Fee {
+        /// The symbol of the security the fee relates to, if any.
+        symbol: Option<String>,
+        /// The quantity charged, such as the coins taken by a crypto fee, if any.
+        qty: Option<Decimal>,
+        /// The amount per share, if any.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Journal entries of cash or stock between accounts.
+    Journal {
+        /// The symbol of the security journaled, for stock journals.
+        symbol: Option<String>,
+        /// The number of shares journaled, for stock journals.
+        qty: Option<Decimal>,
+        /// The amount per share, if any.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Stock splits.
+    Split {
+        /// The symbol of the security that split.
+        symbol: String,
+        /// The change in the number of shares held.
+        qty: Decimal,
+        /// The price per share reported for the split.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Stock spinoffs.
+    Spinoff {
+        /// The symbol of the security received.
+        symbol: String,
+        /// The number of shares received.
+        qty: Decimal,
+        /// The cost basis per share assigned to the shares received.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Mergers, acquisitions and reorganizations.
+    Merger {
+        /// The symbol of the security involved.
+        symbol: String,
+        /// The change in the number of shares held.
+        qty: Option<Decimal>,
+        /// The price or cash amount per share.
         per_share_amount: Option<Decimal>,
     },
+    /// Cash deposits and withdrawals, and ACATS transfers of cash or securities.
+    Transfer {
+        /// The symbol of the security transferred, for securities transfers.
+        symbol: Option<String>,
+        /// The number of shares transferred, for securities transfers.
+        qty: Option<Decimal>,
+        /// The amount per share, if any.
+        per_share_amount: Option<Decimal>,
+    },
+    /// Any other activity, including activity types unknown to this library.
+    Other {
+        /// The symbol of the security involved, if any.
+        symbol: Option<String>,
+        /// The number of shares involved, if any.
+        qty: Option<Decimal>,
+        /// The amount per share, if any.
+        per_share_amount: Option<Decimal>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct RawNonTradeActivity {
+    activity_type: ActivityType,
+    id: String,
+    date: NaiveDate,
+    net_amount: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    qty: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    per_share_amount: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+impl From<RawNonTradeActivity> for NonTradeActivity {
+    fn from(raw: RawNonTradeActivity) -> Self {
+        use ActivityType::*;
+        let RawNonTradeActivity {
+            symbol,
+            qty,
+            per_share_amount,
+            ..
+        } = raw;
+        let details = match (&raw.activity_type, symbol, qty) {
+            (
+                Dividend
+                | DividendLongTermCapitalGain
+                | DividendShortTermCapitalGain
+                | DividendForeignTaxWithheld
+                | DividendNraWithheld
+                | DividendReturnOfCapital
+                | DividendTefraWithheld
+                | DividendTaxExempt,
+                Some(symbol),
+                qty,
+            ) => ActivityDetails::Dividend {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (Interest | InterestNraWithheld | InterestTefraWithheld, symbol, qty) => {
+                ActivityDetails::Interest {
+                    symbol,
+                    qty,
+                    per_share_amount,
+                }
+            }
+            (
+                Fee | CryptoFee | DividendFee | PassThroughCharge | PassThroughRebate,
+                symbol,
+                qty,
+            ) => ActivityDetails::Fee {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (JournalEntry | JournalEntryCash | JournalEntryStock, symbol, qty) => {
+                ActivityDetails::Journal {
+                    symbol,
+                    qty,
+                    per_share_amount,
+                }
+            }
+            (StockSplit, Some(symbol), Some(qty)) => ActivityDetails::Split {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (StockSpinoff, Some(symbol), Some(qty)) => ActivityDetails::Spinoff {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (MergerAcquisition | Reorgnization, Some(symbol), qty) => ActivityDetails::Merger {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (
+                CashTransactions | CashDeposit | CashWithdrawal | AcatsCash | AcatsSecurities,
+                symbol,
+                qty,
+            ) => ActivityDetails::Transfer {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+            (_, symbol, qty) => ActivityDetails::Other {
+                symbol,
+                qty,
+                per_share_amount,
+            },
+        };
+        NonTradeActivity {
+            activity_type: raw.activity_type,
+            id: raw.id,
+            date: raw.date,
+            net_amount: raw.net_amount,
+            description: raw.description,
+            details,
+        }
+    }
+}
+
+impl From<NonTradeActivity> for RawNonTradeActivity {
+    fn from(activity: NonTradeActivity) -> Self {
+        let (symbol, qty, per_share_amount) = match activity.details {
+            ActivityDetails::Dividend {
+                symbol,
+                qty,
+                per_share_amount,
+            } => (Some(symbol), qty, per_share_amount),
+            ActivityDetails::Split {
+                symbol,
+                qty,
+                per_share_amount,
+            }
+            | ActivityDetails::Spinoff {
+                symbol,
+                qty,
+                per_share_amount,
+            } => (Some(symbol), Some(qty), per_share_amount),
+            ActivityDetails::Merger {
+                symbol,
+                qty,
+                per_share_amount,
+            } => (Some(symbol), qty, per_share_amount),
+            ActivityDetails::Interest {
+                symbol,
+                qty,
+                per_share_amount,
+            }
+            | ActivityDetails::Fee {
+                symbol,
+                qty,
+                per_share_amount,
+            }
+            | ActivityDetails::Journal {
+                symbol,
+                qty,
+                per_share_amount,
+            }
+            | ActivityDetails::Transfer {
+                symbol,
+                qty,
+                per_share_amount,
+            }
+            | ActivityDetails::Other {
+                symbol,
+                qty,
+                per_share_amount,
+            } => (symbol, qty, per_share_amount),
+        };
+        RawNonTradeActivity {
+            activity_type: activity.activity_type,
+            id: activity.id,
+            date: activity.date,
+            net_amount: activity.net_amount,
+            symbol,
+            qty,
+            per_share_amount,
+            description: activity.description,
+        }
+    }
 }
 
 impl Activity {
@@ -100,113 +367,174 @@ impl Activity {
     fn id(&self) -> &str {
         match self {
             Activity::TradeActivity { id, .. } => id,
-            Activity::NonTradeActivity { id, .. } => id,
+            Activity::NonTradeActivity(activity) => &activity.id,
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
 /// The types of activities that may be reported
 pub enum ActivityType {
-    #[serde(rename = "FILL")]
     /// Order fills (both partial and full fills)
     Fill,
-    #[serde(rename = "TRANS")]
     /// Cash transactions (both CashDeposit and CashWithdrawal)
     CashTransactions,
-    #[serde(rename = "MISC")]
     /// Miscellaneous or rarely used activity types (All types except those in CashTransactions, Dividend, or Fill)
     Miscellaneous,
-    #[serde(rename = "ACATC")]
     /// ACATS IN/OUT (Cash)
     AcatsCash,
-    #[serde(rename = "ACATS")]
     /// ACATS IN/OUT (Securities)
     AcatsSecurities,
-    #[serde(rename = "CSD")]
+    /// Crypto fee
+    CryptoFee,
     /// Cash deposit(+)
     CashDeposit,
-    #[serde(rename = "CSW")]
     /// Cash withdrawal(-)
     CashWithdrawal,
-    #[serde(rename = "DIV")]
     /// Dividends
     Dividend,
-    #[serde(rename = "DIVCGL")]
     /// Dividend (capital gain long term)
     DividendLongTermCapitalGain,
-    #[serde(rename = "DIVCGS")]
     /// Dividend (capital gain short term)
     DividendShortTermCapitalGain,
-    #[serde(rename = "DIVFEE")]
     /// Dividend fee
     DividendFee,
-    #[serde(rename = "DIVFT")]
     /// Dividend adjusted (Foreign Tax Withheld)
     DividendForeignTaxWithheld,
-    #[serde(rename = "DIVNRA")]
     /// Dividend adjusted (NRA Withheld)
     DividendNraWithheld,
-    #[serde(rename = "DIVROC")]
     /// Dividend return of capital
     DividendReturnOfCapital,
-    #[serde(rename = "DIVTXEX")]
     /// Dividend adjusted (Tefra Withheld)
     DividendTefraWithheld,
-    #[serde(rename = "DIVTXEX")]
     /// Dividend (tax exempt)
     DividendTaxExempt,
-    #[serde(rename = "INT")]
+    /// Fee denominated in USD, such as regulatory fees
+    Fee,
     /// Interest (credit/margin)
     Interest,
-    #[serde(rename = "INTNRA")]
     /// Interest adjusted (NRA Withheld)
     InterestNraWithheld,
-    #[serde(rename = "INTTW")]
     /// Interest adjusted (Tefra Withheld)
     InterestTefraWithheld,
-    #[serde(rename = "JNL")]
     /// Journal entry
     JournalEntry,
-    #[serde(rename = "JNLC")]
     /// Journal entry (cash)
     JournalEntryCash,
-    #[serde(rename = "JNLS")]
     /// Journal entry (stock)
     JournalEntryStock,
-    #[serde(rename = "MA")]
     /// Merger/Acquisition
     MergerAcquisition,
-    #[serde(rename = "NC")]
     /// Name change
     NameChange,
-    #[serde(rename = "OPASN")]
     /// Option assignment
     OptionAssignment,
-    #[serde(rename = "OPEXP")]
     /// Option expiration
     OptionExpiration,
-    #[serde(rename = "OPXRC")]
     /// Option exercise
     OptionExercise,
-    #[serde(rename = "PTC")]
     /// Pass through charge
     PassThroughCharge,
-    #[serde(rename = "PTR")]
     /// Pass through rebate
     PassThroughRebate,
-    #[serde(rename = "REORG")]
     /// Reorg CA
     Reorgnization,
-    #[serde(rename = "SC")]
     /// Symbol change
     SymbolChange,
-    #[serde(rename = "SSO")]
     /// Stock spinoff
     StockSpinoff,
-    #[serde(rename = "SSP")]
     /// Stock split
     StockSplit,
+    /// An activity type unknown to this library, holding the type reported by Alpaca
+    Unknown(String),
+}
+
+impl From<String> for ActivityType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "FILL" => ActivityType::Fill,
+            "TRANS" => ActivityType::CashTransactions,
+            "MISC" => ActivityType::Miscellaneous,
+            "ACATC" => ActivityType::AcatsCash,
+            "ACATS" => ActivityType::AcatsSecurities,
+            "CFEE" => ActivityType::CryptoFee,
+            "CSD" => ActivityType::CashDeposit,
+            "CSW" => ActivityType::CashWithdrawal,
+            "DIV" => ActivityType::Dividend,
+            "DIVCGL" => ActivityType::DividendLongTermCapitalGain,
+            "DIVCGS" => ActivityType::DividendShortTermCapitalGain,
+            "DIVFEE" => ActivityType::DividendFee,
+            "DIVFT" => ActivityType::DividendForeignTaxWithheld,
+            "DIVNRA" => ActivityType::DividendNraWithheld,
+            "DIVROC" => ActivityType::DividendReturnOfCapital,
+            "DIVTW" => ActivityType::DividendTefraWithheld,
+            "DIVTXEX" => ActivityType::DividendTaxExempt,
+            "FEE" => ActivityType::Fee,
+            "INT" => ActivityType::Interest,
+            "INTNRA" => ActivityType::InterestNraWithheld,
+            "INTTW" => ActivityType::InterestTefraWithheld,
+            "JNL" => ActivityType::JournalEntry,
+            "JNLC" => ActivityType::JournalEntryCash,
+            "JNLS" => ActivityType::JournalEntryStock,
+            "MA" => ActivityType::MergerAcquisition,
+            "NC" => ActivityType::NameChange,
+            "OPASN" => ActivityType::OptionAssignment,
+            "OPEXP" => ActivityType::OptionExpiration,
+            "OPXRC" => ActivityType::OptionExercise,
+            "PTC" => ActivityType::PassThroughCharge,
+            "PTR" => ActivityType::PassThroughRebate,
+            "REORG" => ActivityType::Reorgnization,
+            "SC" => ActivityType::SymbolChange,
+            "SSO" => ActivityType::StockSpinoff,
+            "SSP" => ActivityType::StockSplit,
+            _ => ActivityType::Unknown(s),
+        }
+    }
+}
+
+impl From<ActivityType> for String {
+    fn from(activity_type: ActivityType) -> Self {
+        match activity_type {
+            ActivityType::Fill => "FILL".into(),
+            ActivityType::CashTransactions => "TRANS".into(),
+            ActivityType::Miscellaneous => "MISC".into(),
+            ActivityType::AcatsCash => "ACATC".into(),
+            ActivityType::AcatsSecurities => "ACATS".into(),
+            ActivityType::CryptoFee => "CFEE".into(),
+            ActivityType::CashDeposit => "CSD".into(),
+            ActivityType::CashWithdrawal => "CSW".into(),
+            ActivityType::Dividend => "DIV".into(),
+            ActivityType::DividendLongTermCapitalGain => "DIVCGL".into(),
+            ActivityType::DividendShortTermCapitalGain => "DIVCGS".into(),
+            ActivityType::DividendFee => "DIVFEE".into(),
+            ActivityType::DividendForeignTaxWithheld => "DIVFT".into(),
+            ActivityType::DividendNraWithheld => "DIVNRA".into(),
+            ActivityType::DividendReturnOfCapital => "DIVROC".into(),
+            ActivityType::DividendTefraWithheld => "DIVTW".into(),
+            ActivityType::DividendTaxExempt => "DIVTXEX".into(),
+            ActivityType::Fee => "FEE".into(),
+            ActivityType::Interest => "INT".into(),
+            ActivityType::InterestNraWithheld => "INTNRA".into(),
+            ActivityType::InterestTefraWithheld => "INTTW".into(),
+            ActivityType::JournalEntry => "JNL".into(),
+            ActivityType::JournalEntryCash => "JNLC".into(),
+            ActivityType::JournalEntryStock => "JNLS".into(),
+            ActivityType::MergerAcquisition => "MA".into(),
+            ActivityType::NameChange => "NC".into(),
+            ActivityType::OptionAssignment => "OPASN".into(),
+            ActivityType::OptionExpiration => "OPEXP".into(),
+            ActivityType::OptionExercise => "OPXRC".into(),
+            ActivityType::PassThroughCharge => "PTC".into(),
+            ActivityType::PassThroughRebate => "PTR".into(),
+            ActivityType::Reorgnization => "REORG".into(),
+            ActivityType::SymbolChange => "SC".into(),
+            ActivityType::StockSpinoff => "SSO".into(),
+            ActivityType::StockSplit => "SSP".into(),
+            ActivityType::Unknown(s) => s,
+        }
+    }
 }
 
 impl std::fmt::Display for ActivityType {
@@ -342,7 +670,8 @@ impl GetAccountActivities {
         self
     }
 
-    /// Specify the id of the activity from where the pagination should begin.
+    /// Specify the id of the activity from where the pagination should begin. This should not be
+    /// set on requests sent with `send_paginated`, which manages the page token itself.
     pub fn page_token(mut self, page_token: String) -> Self {
         self.page_token = Some(page_token);
         self
@@ -465,7 +794,8 @@ impl GetAccountActivitiesByType {
         self
     }
 
-    /// Specify the id of the activity from where the pagination should begin.
+    /// Specify the id of the activity from where the pagination should begin. This should not be
+    /// set on requests sent with `send_paginated`, which manages the page token itself.
     pub fn page_token(mut self, page_token: String) -> Self {
         self.page_token = Some(page_token);
         self
@@ -485,36 +815,57 @@ impl Request for GetAccountActivitiesByType {
     }
 }
 
+/// The maximum number of activities returned in a single page.
+const MAX_PAGE_SIZE: usize = 100;
+
 #[derive(Clone, Debug)]
 // TODO: Find way in Vila to make this struct private
 /// Struct used for pagination. Users should never interact with this struct directly, but it is
 /// used under the hood when sending a paginated request.
 pub struct AccountActivitiesPage {
-    page_size: usize,
     page_token: String,
 }
 
 impl From<AccountActivitiesPage> for QueryModifier {
     fn from(page: AccountActivitiesPage) -> QueryModifier {
         let mut data = HashMap::new();
-        data.insert("page_size".into(), page.page_size.to_string());
-        data.insert("page_token".into(), page.page_token.into());
+        data.insert("page_token".into(), page.page_token);
         QueryModifier { data }
     }
 }
 
+/// Paginator shared by the account activities requests. Each page starts after the last activity of
+/// the previous page, in the direction of the request's sort order, and keeps the request's filters
+/// and page size. Pagination stops at the first page that is not full.
+fn activities_paginator(
+    page_size: Option<usize>,
+) -> QueryPaginator<Vec<Activity>, AccountActivitiesPage> {
+    let page_size = page_size.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    QueryPaginator::new(
+        move |_: Option<&AccountActivitiesPage>, res: &Vec<Activity>| {
+            if res.len() < page_size {
+                return None;
+            }
+            res.last().map(|x| AccountActivitiesPage {
+                page_token: x.id().to_string(),
+            })
+        },
+    )
+}
+
+impl PaginatedRequest for GetAccountActivities {
+    type Data = AccountActivitiesPage;
+    type Paginator = QueryPaginator<Self::Response, AccountActivitiesPage>;
+    fn paginator(&self) -> Self::Paginator {
+        activities_paginator(self.page_size)
+    }
+}
+
 impl PaginatedRequest for GetAccountActivitiesByType {
     type Data = AccountActivitiesPage;
     type Paginator = QueryPaginator<Self::Response, AccountActivitiesPage>;
     fn paginator(&self) -> Self::Paginator {
-        QueryPaginator::new(
-            |prev: Option<&AccountActivitiesPage>, res: &Vec<Activity>| {
-                res.last().map(|x| AccountActivitiesPage {
-                    page_size: prev.map(|y| y.page_size).unwrap_or(100),
-                    page_token: x.id().to_string(),
-                })
-            },
-        )
+        activities_paginator(self.page_size)
     }
 }
 
@@ -522,11 +873,13 @@ impl PaginatedRequest for GetAccountActivitiesByType {
 mod test {
     use super::*;
     use crate::client_with_url;
-    use mockito::mock;
+    use crate::test_fixtures::{with_fields, NON_TRADE_ACTIVITY, TRADE_ACTIVITY};
+    use futures::StreamExt;
+    use mockito::{mock, Matcher};
 
     #[tokio::test]
     async fn get_account_activities() {
-        let both_activities = format!("[{},{}]", TRADE_ACTIVITY, NONTRADE_ACTIVITY);
+        let both_activities = format!("[{},{}]", TRADE_ACTIVITY, DIVIDEND_ACTIVITY);
         let _m = mock("GET", "/v2/account/activities")
             .match_header("apca-api-key-id", "APCA_API_KEY_ID")
             .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
@@ -562,7 +915,7 @@ mod test {
 
     #[tokio::test]
     async fn get_account_activities_by_type() {
-        let div_activities = format!("[{}]", NONTRADE_ACTIVITY);
+        let div_activities = format!("[{}]", DIVIDEND_ACTIVITY);
         let _m = mock("GET", "/v2/account/activities/DIV")
             .match_header("apca-api-key-id", "APCA_API_KEY_ID")
             .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
@@ -575,20 +928,234 @@ mod test {
         client.send(&req).await.unwrap();
     }
 
-    const TRADE_ACTIVITY: &'static str = r#"{
-  		"activity_type": "FILL",
-  		"cum_qty": "1",
-  		"id": "20190524113406977::8efc7b9a-8b2b-4000-9955-d36e7db0df74",
-  		"leaves_qty": "0",
-  		"price": "1.63",
-  		"qty": "1",
-  		"side": "buy",
-  		"symbol": "LPCN",
-  		"transaction_time": "2019-05-24T15:34:06.977Z",
-  		"order_id": "904837e3-3b76-47ec-b432-046db621571b",
-  		"type": "fill"
-	}"#;
-    const NONTRADE_ACTIVITY: &'static str = r#"{
+    #[tokio::test]
+    async fn get_account_activities_paginated() {
+        let first_page = format!("[{},{}]", TRADE_ACTIVITY, DIVIDEND_ACTIVITY);
+        let second_page = format!("[{}]", TRADE_ACTIVITY);
+        let _first = mock("GET", "/v2/account/activities")
+            .match_query("after=2019-01-01&direction=asc&page_size=2")
+            .with_body(first_page)
+            .create();
+        let _second = mock("GET", "/v2/account/activities")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("direction".into(), "asc".into()),
+                Matcher::UrlEncoded("page_size".into(), "2".into()),
+                Matcher::UrlEncoded(
+                    "page_token".into(),
+                    "20190801011955195::5f596936-6f23-4cef-bdf1-3806aae57dbf".into(),
+                ),
+            ]))
+            .with_body(second_page)
+            .expect(1)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let req = GetAccountActivities::new()
+            .after_date(NaiveDate::from_ymd_opt(2019, 1, 1).unwrap())
+            .sort(Sort::Ascending)
+            .page_size(2);
+        let pages: Vec<Vec<Activity>> = client
+            .send_paginated(&req)
+            .map(|page| page.unwrap())
+            .collect()
+            .await;
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 1]);
+        _second.assert();
+    }
+
+    #[test]
+    fn activity_type_codes() {
+        let codes = [
+            "FILL", "TRANS", "MISC", "ACATC", "ACATS", "CFEE", "CSD", "CSW", "DIV", "DIVCGL",
+            "DIVCGS", "DIVFEE", "DIVFT", "DIVNRA", "DIVROC", "DIVTW", "DIVTXEX", "FEE", "INT",
+            "INTNRA", "INTTW", "JNL", "JNLC", "JNLS", "MA", "NC", "OPASN", "OPEXP", "OPXRC", "PTC",
+            "PTR", "REORG", "SC", "SSO", "SSP",
+        ];
+        let types: Vec<ActivityType> = codes
+            .iter()
+            .map(|code| serde_json::from_value(serde_json::json!(code)).unwrap())
+            .collect();
+        for (code, activity_type) in codes.iter().zip(&types) {
+            assert!(
+                !matches!(activity_type, ActivityType::Unknown(_)),
+                "{}",
+                code
+            );
+            assert_eq!(&activity_type.to_string(), code);
+        }
+        let distinct: std::collections::HashSet<_> = types.iter().collect();
+        assert_eq!(distinct.len(), codes.len());
+        assert_eq!(types[15], ActivityType::DividendTefraWithheld);
+        assert_eq!(types[16], ActivityType::DividendTaxExempt);
+
+        let unknown: ActivityType = serde_json::from_str(r#""NEWTYPE""#).unwrap();
+        assert_eq!(unknown, ActivityType::Unknown("NEWTYPE".into()));
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), r#""NEWTYPE""#);
+    }
+
+    fn non_trade(fields: serde_json::Value) -> Activity {
+        let parsed: Activity = with_fields(NON_TRADE_ACTIVITY, fields);
+        // Serializing and parsing again must give the same activity.
+        let round_trip = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(
+            serde_json::from_value::<Activity>(round_trip).unwrap(),
+            parsed
+        );
+        parsed
+    }
+
+    fn details(activity: Activity) -> ActivityDetails {
+        match activity {
+            Activity::NonTradeActivity(activity) => activity.details,
+            Activity::TradeActivity { .. } => panic!("expected a non-trade activity"),
+        }
+    }
+
+    #[test]
+    fn non_trade_activity_round_trip() {
+        use serde_json::json;
+        for fields in [
+            json!({"activity_type": "CFEE", "symbol": "BTCUSD", "qty": "-0.0001", "per_share_amount": "20000"}),
+            json!({"activity_type": "JNLS", "symbol": "AAPL", "qty": "1.5", "per_share_amount": "150"}),
+            json!({"activity_type": "INT", "symbol": "USD", "qty": "1000", "per_share_amount": "0.0001"}),
+        ] {
+            let expected: serde_json::Value = with_fields(NON_TRADE_ACTIVITY, fields);
+            let activity: Activity = serde_json::from_value(expected.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&activity).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn non_trade_activity_details() {
+        use serde_json::json;
+        let d = |s: &str| s.parse::<Decimal>().unwrap();
+
+        assert_eq!(
+            details(serde_json::from_str(DIVIDEND_ACTIVITY).unwrap()),
+            ActivityDetails::Dividend {
+                symbol: "T".into(),
+                qty: Some(d("2")),
+                per_share_amount: Some(d("0.51")),
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "DIVNRA", "symbol": "T", "net_amount": "-0.15"})
+            )),
+            ActivityDetails::Dividend {
+                symbol: "T".into(),
+                qty: None,
+                per_share_amount: None,
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "INT", "net_amount": "0.12"})
+            )),
+            ActivityDetails::Interest {
+                symbol: None,
+                qty: None,
+                per_share_amount: None,
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "FEE", "net_amount": "-0.01", "description": "REG/TAF fee"})
+            )),
+            ActivityDetails::Fee {
+                symbol: None,
+                qty: None,
+                per_share_amount: None,
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "CFEE", "symbol": "BTCUSD", "qty": "-0.0001", "net_amount": "0"})
+            )),
+            ActivityDetails::Fee {
+                symbol: Some("BTCUSD".into()),
+                qty: Some(d("-0.0001")),
+                per_share_amount: None,
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "JNLS", "symbol": "AAPL", "qty": "1.5"})
+            )),
+            ActivityDetails::Journal {
+                symbol: Some("AAPL".into()),
+                qty: Some(d("1.5")),
+                per_share_amount: None,
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "SSP", "symbol": "AAPL", "qty": "30", "per_share_amount": "125.5"})
+            )),
+            ActivityDetails::Split {
+                symbol: "AAPL".into(),
+                qty: d("30"),
+                per_share_amount: Some(d("125.5")),
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "SSO", "symbol": "KD", "qty": "0.2", "per_share_amount": "30"})
+            )),
+            ActivityDetails::Spinoff {
+                symbol: "KD".into(),
+                qty: d("0.2"),
+                per_share_amount: Some(d("30")),
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "MA", "symbol": "TWTR", "qty": "-10", "per_share_amount": "54.2"})
+            )),
+            ActivityDetails::Merger {
+                symbol: "TWTR".into(),
+                qty: Some(d("-10")),
+                per_share_amount: Some(d("54.2")),
+            }
+        );
+        assert_eq!(
+            details(non_trade(
+                json!({"activity_type": "CSD", "net_amount": "1000"})
+            )),
+            ActivityDetails::Transfer {
+                symbol: None,
+                qty: None,
+                per_share_amount: None,
+            }
+        );
+        // Activities missing the fields of their category, and unknown types, fall back to Other.
+        assert_eq!(
+            details(non_trade(json!({"activity_type": "SSP", "symbol": "AAPL"}))),
+            ActivityDetails::Other {
+                symbol: Some("AAPL".into()),
+                qty: None,
+                per_share_amount: None,
+            }
+        );
+        let activity = non_trade(json!({"activity_type": "NEWTYPE", "qty": "1"}));
+        match &activity {
+            Activity::NonTradeActivity(a) => {
+                assert_eq!(a.activity_type, ActivityType::Unknown("NEWTYPE".into()))
+            }
+            Activity::TradeActivity { .. } => panic!("expected a non-trade activity"),
+        }
+        assert_eq!(
+            details(activity),
+            ActivityDetails::Other {
+                symbol: None,
+                qty: Some(d("1")),
+                per_share_amount: None,
+            }
+        );
+    }
+
+    const DIVIDEND_ACTIVITY: &'static str = r#"{
   		"activity_type": "DIV",
   		"id": "20190801011955195::5f596936-6f23-4cef-bdf1-3806aae57dbf",
   		"date": "2019-08-01",
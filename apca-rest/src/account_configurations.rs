@@ -1,10 +1,12 @@
-use serde::{Deserialize, Serialize};
+use crate::utils::{from_str_optional, to_string_optional};
+use serde::{Deserialize, Serialize, Serializer};
 use std::borrow::Cow;
-use vila::{Method, Request, RequestData};
+use std::fmt;
+use vila::{Client, Method, Request, RequestData};
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
-/// Controls Day Trading Margin Call (DTMC) checks.
+/// Controls when Day Trading Margin Call (DTMC) and pattern day trader checks are made.
 pub enum DtbpCheck {
     /// Check on both entry and exit
     Both,
@@ -20,7 +22,7 @@ impl Default for DtbpCheck {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 /// If `Zero`, emails for order fills are not sent.
 pub enum TradeConfirmEmail {
@@ -37,7 +39,7 @@ impl Default for TradeConfirmEmail {
     }
 }
 
-#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq, Eq)]
 /// AccountConfigurations object
 pub struct AccountConfigurations {
     /// Controls Day Trading Margin Call (DTMC) checks.
@@ -48,6 +50,26 @@ pub struct AccountConfigurations {
     pub suspend_trade: bool,
     /// If true, account becomes long-only mode.
     pub no_shorting: bool,
+    #[serde(default)]
+    /// If true, the account can trade fractional shares.
+    pub fractional_trading: bool,
+    #[serde(
+        default,
+        deserialize_with = "from_str_optional",
+        serialize_with = "to_string_optional"
+    )]
+    /// The maximum margin multiplier of the account, either 1 or 2.
+    pub max_margin_multiplier: Option<u8>,
+    #[serde(default)]
+    /// Controls when pattern day trader checks are made.
+    pub pdt_check: DtbpCheck,
+    #[serde(default)]
+    /// If true, orders for publicly traded partnerships are accepted without the 10% withholding
+    /// exception.
+    pub ptp_no_exception_entry: bool,
+    #[serde(default)]
+    /// The highest options trading level allowed for the account, between 0 and 3.
+    pub max_options_trading_level: Option<u8>,
 }
 
 #[derive(Clone, Debug)]
@@ -77,8 +99,9 @@ impl Request for GetAccountConfigurations {
     }
 }
 
-#[derive(Clone, Debug, Default, Serialize)]
-/// Updates the current account configuration values
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+/// Updates the current account configuration values. Only the fields which are set are sent, and
+/// the others are left unchanged.
 ///
 /// # Examples
 /// ```no_run
@@ -102,12 +125,29 @@ impl Request for GetAccountConfigurations {
 ///         .await?;
 ///     Ok(())
 /// }
-// TODO: The Alpaca docs here are wrong
+/// ```
 pub struct PatchAccountConfigurations {
+    #[serde(skip_serializing_if = "Option::is_none")]
     dtbp_check: Option<DtbpCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     trade_confirm_email: Option<TradeConfirmEmail>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     suspend_trade: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     no_shorting: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fractional_trading: Option<bool>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "to_string_clearable"
+    )]
+    max_margin_multiplier: Option<Option<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pdt_check: Option<DtbpCheck>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ptp_no_exception_entry: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_options_trading_level: Option<Option<u8>>,
 }
 
 impl PatchAccountConfigurations {
@@ -139,6 +179,81 @@ impl PatchAccountConfigurations {
         self.no_shorting = Some(no_shorting);
         self
     }
+
+    /// If true, the account can trade fractional shares.
+    pub fn fractional_trading(mut self, fractional_trading: bool) -> Self {
+        self.fractional_trading = Some(fractional_trading);
+        self
+    }
+
+    /// The maximum margin multiplier of the account, either 1 or 2. `None` clears the setting.
+    pub fn max_margin_multiplier(mut self, max_margin_multiplier: Option<u8>) -> Self {
+        self.max_margin_multiplier = Some(max_margin_multiplier);
+        self
+    }
+
+    /// Controls when pattern day trader checks are made.
+    pub fn pdt_check(mut self, pdt_check: DtbpCheck) -> Self {
+        self.pdt_check = Some(pdt_check);
+        self
+    }
+
+    /// If true, orders for publicly traded partnerships are accepted without the 10% withholding
+    /// exception.
+    pub fn ptp_no_exception_entry(mut self, ptp_no_exception_entry: bool) -> Self {
+        self.ptp_no_exception_entry = Some(ptp_no_exception_entry);
+        self
+    }
+
+    /// The highest options trading level allowed for the account, between 0 and 3. `None` clears
+    /// the setting.
+    pub fn max_options_trading_level(mut self, max_options_trading_level: Option<u8>) -> Self {
+        self.max_options_trading_level = Some(max_options_trading_level);
+        self
+    }
+
+    /// Create a request that sets only the fields that differ between `current` and `desired`.
+    /// Optional fields that are cleared in `desired` are sent as null.
+    pub fn diff(current: &AccountConfigurations, desired: &AccountConfigurations) -> Self {
+        fn changed<T: Clone + PartialEq>(current: &T, desired: &T) -> Option<T> {
+            (current != desired).then(|| desired.clone())
+        }
+        Self {
+            dtbp_check: changed(&current.dtbp_check, &desired.dtbp_check),
+            trade_confirm_email: changed(
+                &current.trade_confirm_email,
+                &desired.trade_confirm_email,
+            ),
+            suspend_trade: changed(&current.suspend_trade, &desired.suspend_trade),
+            no_shorting: changed(&current.no_shorting, &desired.no_shorting),
+            fractional_trading: changed(&current.fractional_trading, &desired.fractional_trading),
+            max_margin_multiplier: changed(
+                &current.max_margin_multiplier,
+                &desired.max_margin_multiplier,
+            ),
+            pdt_check: changed(&current.pdt_check, &desired.pdt_check),
+            ptp_no_exception_entry: changed(
+                &current.ptp_no_exception_entry,
+                &desired.ptp_no_exception_entry,
+            ),
+            max_options_trading_level: changed(
+                &current.max_options_trading_level,
+                &desired.max_options_trading_level,
+            ),
+        }
+    }
+
+    /// Whether the request leaves every configuration unchanged.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+fn to_string_clearable<S: Serializer>(
+    value: &Option<Option<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    to_string_optional(&value.flatten(), serializer)
 }
 
 impl Request for PatchAccountConfigurations {
@@ -155,6 +270,91 @@ impl Request for PatchAccountConfigurations {
     }
 }
 
+#[derive(Debug)]
+/// An error updating the account configurations.
+pub enum ConfigurationError {
+    /// A request failed.
+    Request(vila::Error),
+    /// The update was accepted, but the configurations returned do not match those requested.
+    NotApplied {
+        /// The requested configurations
+        requested: Box<AccountConfigurations>,
+        /// The configurations returned by the update
+        actual: Box<AccountConfigurations>,
+    },
+}
+
+impl fmt::Display for ConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigurationError::Request(e) => write!(f, "request failed: {}", e),
+            ConfigurationError::NotApplied { requested, actual } => write!(
+                f,
+                "configurations not applied: requested {:?}, got {:?}",
+                requested, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigurationError {}
+
+impl From<vila::Error> for ConfigurationError {
+    fn from(e: vila::Error) -> Self {
+        ConfigurationError::Request(e)
+    }
+}
+
+/// Fetch the current account configurations, apply `f` to them and send only the fields that
+/// changed, then verify that the configurations returned match those requested. No update is sent
+/// if `f` leaves the configurations unchanged.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{account_configurations::update_configurations, paper_client};
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     update_configurations(&client, |config| config.no_shorting = true).await?;
+///     Ok(())
+/// }
+/// ```
+pub async fn update_configurations<F>(
+    client: &Client,
+    f: F,
+) -> Result<AccountConfigurations, ConfigurationError>
+where
+    F: FnOnce(&mut AccountConfigurations),
+{
+    let current = client.send(&GetAccountConfigurations).await?;
+    let mut requested = current.clone();
+    f(&mut requested);
+    let patch = PatchAccountConfigurations::diff(&current, &requested);
+    if patch.is_empty() {
+        return Ok(current);
+    }
+    let actual = client.send(&patch).await?;
+    if actual == requested {
+        Ok(actual)
+    } else {
+        Err(ConfigurationError::NotApplied {
+            requested: Box::new(requested),
+            actual: Box::new(actual),
+        })
+    }
+}
+
+/// Kill switch that blocks all new orders by setting `suspend_trade`, and confirms that it took
+/// effect. Orders that are already open are not canceled.
+pub async fn suspend_trading(client: &Client) -> Result<AccountConfigurations, ConfigurationError> {
+    update_configurations(client, |config| config.suspend_trade = true).await
+}
+
+/// Allow new orders again after `suspend_trading`, and confirm that it took effect.
+pub async fn resume_trading(client: &Client) -> Result<AccountConfigurations, ConfigurationError> {
+    update_configurations(client, |config| config.suspend_trade = false).await
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -209,4 +409,115 @@ mod test {
             .await
             .unwrap();
     }
+
+    const CONFIGURATIONS: &'static str = r#"{
+        "dtbp_check": "both",
+        "trade_confirm_email": "none",
+        "suspend_trade": false,
+        "no_shorting": false,
+        "fractional_trading": true,
+        "max_margin_multiplier": "2",
+        "pdt_check": "entry",
+        "ptp_no_exception_entry": false,
+        "max_options_trading_level": 1
+    }"#;
+
+    #[tokio::test]
+    async fn update_configurations_sends_diff() {
+        let _get = mock("GET", "/v2/account/configurations")
+            .with_body(CONFIGURATIONS)
+            .create();
+        let _patch = mock("PATCH", "/v2/account/configurations")
+            .match_body(r#"{"no_shorting":true,"max_margin_multiplier":"1"}"#)
+            .with_body(
+                CONFIGURATIONS
+                    .replace(r#""no_shorting": false"#, r#""no_shorting": true"#)
+                    .replace(
+                        r#""max_margin_multiplier": "2""#,
+                        r#""max_margin_multiplier": "1""#,
+                    ),
+            )
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let config = update_configurations(&client, |config| {
+            config.no_shorting = true;
+            config.max_margin_multiplier = Some(1);
+        })
+        .await
+        .unwrap();
+        assert!(config.no_shorting);
+        assert_eq!(config.max_margin_multiplier, Some(1));
+        assert_eq!(config.trade_confirm_email, TradeConfirmEmail::Zero);
+        assert_eq!(config.dtbp_check, DtbpCheck::Both);
+        assert_eq!(config.max_options_trading_level, Some(1));
+        assert!(config.fractional_trading);
+    }
+
+    #[tokio::test]
+    async fn kill_switch_not_applied() {
+        let _get = mock("GET", "/v2/account/configurations")
+            .with_body(CONFIGURATIONS)
+            .create();
+        let _patch = mock("PATCH", "/v2/account/configurations")
+            .match_body(r#"{"suspend_trade":true}"#)
+            .with_body(CONFIGURATIONS)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        match suspend_trading(&client).await {
+            Err(ConfigurationError::NotApplied { requested, actual }) => {
+                assert!(requested.suspend_trade);
+                assert!(!actual.suspend_trade);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn diff_clears_fields() {
+        let current: AccountConfigurations = serde_json::from_str(CONFIGURATIONS).unwrap();
+        let desired = AccountConfigurations {
+            max_margin_multiplier: None,
+            max_options_trading_level: None,
+            ..current.clone()
+        };
+        let patch = PatchAccountConfigurations::diff(&current, &desired);
+        assert_eq!(
+            serde_json::to_string(&patch).unwrap(),
+            r#"{"max_margin_multiplier":null,"max_options_trading_level":null}"#
+        );
+        assert_eq!(
+            serde_json::to_string(
+                &PatchAccountConfigurations::new().max_margin_multiplier(Some(2))
+            )
+            .unwrap(),
+            r#"{"max_margin_multiplier":"2"}"#
+        );
+    }
+
+    #[test]
+    fn numeric_max_margin_multiplier() {
+        let config: AccountConfigurations =
+            serde_json::from_str(&CONFIGURATIONS.replace(r#""2""#, "2")).unwrap();
+        assert_eq!(config.max_margin_multiplier, Some(2));
+        let current: AccountConfigurations = serde_json::from_str(CONFIGURATIONS).unwrap();
+        assert!(PatchAccountConfigurations::diff(&current, &config).is_empty());
+
+        let config: AccountConfigurations =
+            serde_json::from_str(&CONFIGURATIONS.replace(r#""2""#, "null")).unwrap();
+        assert_eq!(config.max_margin_multiplier, None);
+        assert!(serde_json::from_str::<AccountConfigurations>(
+            &CONFIGURATIONS.replace(r#""2""#, "true")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn diff_unchanged() {
+        let config: AccountConfigurations = serde_json::from_str(CONFIGURATIONS).unwrap();
+        assert!(PatchAccountConfigurations::diff(&config, &config).is_empty());
+    }
 }
@@ -0,0 +1,453 @@
+use crate::account::{Account, AccountStatus, GetAccount};
+use crate::orders::{Order, SubmitOrder};
+use futures::stream::{self, Stream, StreamExt};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use vila::Client;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A change to the account observed between two polls.
+pub enum AccountEvent {
+    /// The account status changed.
+    StatusChanged {
+        /// Status at the previous poll
+        previous: AccountStatus,
+        /// Status at the current poll
+        current: AccountStatus,
+    },
+    /// The `trading_blocked` flag was toggled to the given value.
+    TradingBlockedChanged(bool),
+    /// The `account_blocked` flag was toggled to the given value.
+    AccountBlockedChanged(bool),
+    /// The `trade_suspended_by_user` flag was toggled to the given value.
+    TradeSuspendedByUserChanged(bool),
+    /// The `pattern_day_trader` flag was toggled to the given value.
+    PatternDayTraderChanged(bool),
+    /// The number of day trades in the last 5 trading days went up.
+    DaytradeCountIncreased {
+        /// Count at the previous poll
+        previous: u32,
+        /// Count at the current poll
+        current: u32,
+    },
+    /// Equity fell from its peak by more than the drawdown threshold.
+    DrawdownExceeded {
+        /// Highest equity observed by the monitor
+        peak: Decimal,
+        /// Current equity
+        equity: Decimal,
+        /// Fall from the peak, by a factor of 1
+        drawdown: Decimal,
+    },
+    /// Equity recovered to within the drawdown threshold of its peak.
+    DrawdownRecovered {
+        /// Highest equity observed by the monitor
+        peak: Decimal,
+        /// Current equity
+        equity: Decimal,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The reason an order was refused by the monitor.
+pub enum OrderBlocked {
+    /// The account has not been polled yet.
+    NoAccount,
+    /// The account status is not `Active`.
+    Inactive(AccountStatus),
+    /// The account is not allowed to place orders.
+    TradingBlocked,
+    /// Account activity is prohibited.
+    AccountBlocked,
+    /// The user has suspended trading.
+    TradeSuspendedByUser,
+    /// The account has reached the configured day trade limit.
+    DaytradeLimit(u32),
+    /// Equity is in a drawdown beyond the configured threshold.
+    Drawdown(Decimal),
+    /// The order costs more than the available buying power.
+    InsufficientBuyingPower {
+        /// Estimated cost of the order
+        required: Decimal,
+        /// Buying power of the account
+        available: Decimal,
+    },
+}
+
+impl fmt::Display for OrderBlocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderBlocked::NoAccount => write!(f, "account has not been polled yet"),
+            OrderBlocked::Inactive(status) => write!(f, "account status is {:?}", status),
+            OrderBlocked::TradingBlocked => write!(f, "trading is blocked"),
+            OrderBlocked::AccountBlocked => write!(f, "account is blocked"),
+            OrderBlocked::TradeSuspendedByUser => write!(f, "trading is suspended by user"),
+            OrderBlocked::DaytradeLimit(count) => {
+                write!(f, "{} day trades made in the last 5 trading days", count)
+            }
+            OrderBlocked::Drawdown(drawdown) => write!(f, "equity is down {} from peak", drawdown),
+            OrderBlocked::InsufficientBuyingPower {
+                required,
+                available,
+            } => write!(
+                f,
+                "order requires {} buying power, but only {} is available",
+                required, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OrderBlocked {}
+
+#[derive(Debug)]
+/// Errors that can occur when submitting an order through the monitor.
+pub enum SubmitError {
+    /// The order was refused by the monitor and not sent.
+    Blocked(OrderBlocked),
+    /// The order was sent, but the request failed.
+    Request(vila::Error),
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::Blocked(reason) => write!(f, "order blocked: {}", reason),
+            SubmitError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+#[derive(Debug, Default)]
+struct State {
+    account: Option<Account>,
+    peak_equity: Decimal,
+    in_drawdown: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+/// Watches the account for changes and refuses orders while the account is in a state where they
+/// would be rejected, or where the configured limits have been reached.
+///
+/// Alpaca does not stream account updates, so the monitor polls `GetAccount`. Clones of a monitor
+/// share the same state, so one clone can be used to watch the account while others gate orders.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{account_monitor::AccountMonitor, paper_client};
+/// use futures::StreamExt;
+/// use rust_decimal::Decimal;
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let client = paper_client("KEY", "SECRET");
+///     let monitor = AccountMonitor::new()
+///         .drawdown_threshold(Decimal::new(5, 2))
+///         .max_daytrades(3);
+///     let mut events = Box::pin(monitor.watch(&client, Duration::from_secs(60)));
+///     while let Some(event) = events.next().await {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+pub struct AccountMonitor {
+    state: Arc<Mutex<State>>,
+    drawdown_threshold: Option<Decimal>,
+    max_daytrades: Option<u32>,
+}
+
+impl AccountMonitor {
+    /// Create a new monitor with no limits configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report a drawdown, and refuse orders, once equity falls from its peak by more than this
+    /// fraction (by a factor of 1).
+    pub fn drawdown_threshold(mut self, drawdown_threshold: Decimal) -> Self {
+        self.drawdown_threshold = Some(drawdown_threshold);
+        self
+    }
+
+    /// Refuse orders once the account has made this many day trades in the last 5 trading days.
+    pub fn max_daytrades(mut self, max_daytrades: u32) -> Self {
+        self.max_daytrades = Some(max_daytrades);
+        self
+    }
+
+    /// The most recently observed account.
+    pub fn account(&self) -> Option<Account> {
+        self.state.lock().unwrap().account.clone()
+    }
+
+    /// Record a new observation of the account, returning the changes since the previous one.
+    /// The first observation only establishes the baseline and reports no changes, other than a
+    /// drawdown if the account is already past the threshold.
+    pub fn update(&self, account: Account) -> Vec<AccountEvent> {
+        let mut state = self.state.lock().unwrap();
+        let mut events = Vec::new();
+        if let Some(previous) = &state.account {
+            if previous.status != account.status {
+                events.push(AccountEvent::StatusChanged {
+                    previous: previous.status.clone(),
+                    current: account.status.clone(),
+                });
+            }
+            if previous.trading_blocked != account.trading_blocked {
+                events.push(AccountEvent::TradingBlockedChanged(account.trading_blocked));
+            }
+            if previous.account_blocked != account.account_blocked {
+                events.push(AccountEvent::AccountBlockedChanged(account.account_blocked));
+            }
+            if previous.trade_suspended_by_user != account.trade_suspended_by_user {
+                events.push(AccountEvent::TradeSuspendedByUserChanged(
+                    account.trade_suspended_by_user,
+                ));
+            }
+            if previous.pattern_day_trader != account.pattern_day_trader {
+                events.push(AccountEvent::PatternDayTraderChanged(
+                    account.pattern_day_trader,
+                ));
+            }
+            if account.daytrade_count > previous.daytrade_count {
+                events.push(AccountEvent::DaytradeCountIncreased {
+                    previous: previous.daytrade_count,
+                    current: account.daytrade_count,
+                });
+            }
+        }
+
+        if account.equity > state.peak_equity {
+            state.peak_equity = account.equity;
+        }
+        if let Some(threshold) = self.drawdown_threshold {
+            let peak = state.peak_equity;
+            let drawdown = drawdown(peak, account.equity);
+            if drawdown > threshold && !state.in_drawdown {
+                state.in_drawdown = true;
+                events.push(AccountEvent::DrawdownExceeded {
+                    peak,
+                    equity: account.equity,
+                    drawdown,
+                });
+            } else if drawdown <= threshold && state.in_drawdown {
+                state.in_drawdown = false;
+                events.push(AccountEvent::DrawdownRecovered {
+                    peak,
+                    equity: account.equity,
+                });
+            }
+        }
+
+        state.account = Some(account);
+        events
+    }
+
+    /// Fetch the account once and record it, returning the changes since the previous poll.
+    pub async fn poll(&self, client: &Client) -> Result<Vec<AccountEvent>, vila::Error> {
+        let account = client.send(&GetAccount).await?;
+        Ok(self.update(account))
+    }
+
+    /// Poll the account every `period`, yielding each change as it is observed. Request errors
+    /// are yielded without ending the stream.
+    pub fn watch<'a>(
+        &'a self,
+        client: &'a Client,
+        period: Duration,
+    ) -> impl Stream<Item = Result<AccountEvent, vila::Error>> + 'a {
+        let interval = tokio::time::interval(period);
+        stream::unfold(interval, move |mut interval| async move {
+            interval.tick().await;
+            let events = match self.poll(client).await {
+                Ok(events) => events.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            Some((stream::iter(events), interval))
+        })
+        .flatten()
+    }
+
+    /// Check whether an order costing `cost` dollars may be submitted given the most recently
+    /// observed account. Use a cost of zero for orders that do not consume buying power.
+    pub fn check_order(&self, cost: Decimal) -> Result<(), OrderBlocked> {
+        let state = self.state.lock().unwrap();
+        let account = state.account.as_ref().ok_or(OrderBlocked::NoAccount)?;
+        if account.status != AccountStatus::Active {
+            return Err(OrderBlocked::Inactive(account.status.clone()));
+        }
+        if account.account_blocked {
+            return Err(OrderBlocked::AccountBlocked);
+        }
+        if account.trading_blocked {
+            return Err(OrderBlocked::TradingBlocked);
+        }
+        if account.trade_suspended_by_user {
+            return Err(OrderBlocked::TradeSuspendedByUser);
+        }
+        if let Some(max) = self.max_daytrades {
+            if account.daytrade_count >= max {
+                return Err(OrderBlocked::DaytradeLimit(account.daytrade_count));
+            }
+        }
+        if state.in_drawdown {
+            return Err(OrderBlocked::Drawdown(drawdown(
+                state.peak_equity,
+                account.equity,
+            )));
+        }
+        if cost > account.buying_power {
+            return Err(OrderBlocked::InsufficientBuyingPower {
+                required: cost,
+                available: account.buying_power,
+            });
+        }
+        Ok(())
+    }
+
+    /// Submit the order if `check_order` allows it.
+    pub async fn submit(
+        &self,
+        client: &Client,
+        order: &SubmitOrder,
+        cost: Decimal,
+    ) -> Result<Order, SubmitError> {
+        self.check_order(cost).map_err(SubmitError::Blocked)?;
+        client.send(order).await.map_err(SubmitError::Request)
+    }
+}
+
+fn drawdown(peak: Decimal, equity: Decimal) -> Decimal {
+    if peak.is_zero() {
+        Decimal::ZERO
+    } else {
+        (peak - equity) / peak
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client_with_url;
+    use crate::test_fixtures::ACCOUNT;
+    use mockito::mock;
+
+    fn account() -> Account {
+        serde_json::from_str(ACCOUNT).unwrap()
+    }
+
+    #[test]
+    fn events() {
+        let monitor = AccountMonitor::new();
+        assert!(monitor.update(account()).is_empty());
+
+        let mut next = account();
+        next.trading_blocked = true;
+        next.daytrade_count = 2;
+        next.status = AccountStatus::AccountUpdate;
+        assert_eq!(
+            monitor.update(next.clone()),
+            vec![
+                AccountEvent::StatusChanged {
+                    previous: AccountStatus::Active,
+                    current: AccountStatus::AccountUpdate,
+                },
+                AccountEvent::TradingBlockedChanged(true),
+                AccountEvent::DaytradeCountIncreased {
+                    previous: 0,
+                    current: 2
+                },
+            ]
+        );
+        assert!(monitor.update(next).is_empty());
+    }
+
+    #[test]
+    fn drawdown_events() {
+        let monitor = AccountMonitor::new().drawdown_threshold(Decimal::new(1, 1));
+        let mut account = account();
+        account.equity = Decimal::new(1000, 0);
+        monitor.update(account.clone());
+
+        account.equity = Decimal::new(850, 0);
+        assert_eq!(
+            monitor.update(account.clone()),
+            vec![AccountEvent::DrawdownExceeded {
+                peak: Decimal::new(1000, 0),
+                equity: Decimal::new(850, 0),
+                drawdown: Decimal::new(15, 2),
+            }]
+        );
+        assert_eq!(
+            monitor.check_order(Decimal::ZERO),
+            Err(OrderBlocked::Drawdown(Decimal::new(15, 2)))
+        );
+
+        account.equity = Decimal::new(800, 0);
+        assert!(monitor.update(account.clone()).is_empty());
+
+        account.equity = Decimal::new(950, 0);
+        assert_eq!(
+            monitor.update(account),
+            vec![AccountEvent::DrawdownRecovered {
+                peak: Decimal::new(1000, 0),
+                equity: Decimal::new(950, 0),
+            }]
+        );
+        assert_eq!(monitor.check_order(Decimal::ZERO), Ok(()));
+    }
+
+    #[test]
+    fn check_order() {
+        let monitor = AccountMonitor::new().max_daytrades(3);
+        assert_eq!(
+            monitor.check_order(Decimal::ZERO),
+            Err(OrderBlocked::NoAccount)
+        );
+
+        let mut account = account();
+        monitor.update(account.clone());
+        assert_eq!(monitor.check_order(Decimal::new(1000, 0)), Ok(()));
+        assert_eq!(
+            monitor.check_order(Decimal::new(300000, 0)),
+            Err(OrderBlocked::InsufficientBuyingPower {
+                required: Decimal::new(300000, 0),
+                available: Decimal::new(262113632, 3),
+            })
+        );
+
+        account.daytrade_count = 3;
+        monitor.update(account.clone());
+        assert_eq!(
+            monitor.check_order(Decimal::ZERO),
+            Err(OrderBlocked::DaytradeLimit(3))
+        );
+
+        account.trading_blocked = true;
+        monitor.update(account);
+        assert_eq!(
+            monitor.check_order(Decimal::ZERO),
+            Err(OrderBlocked::TradingBlocked)
+        );
+    }
+
+    #[tokio::test]
+    async fn poll() {
+        let _m = mock("GET", "/v2/account")
+            .match_header("apca-api-key-id", "APCA_API_KEY_ID")
+            .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
+            .with_body(ACCOUNT)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let monitor = AccountMonitor::new();
+        assert!(monitor.poll(&client).await.unwrap().is_empty());
+        assert_eq!(monitor.account(), Some(account()));
+    }
+}
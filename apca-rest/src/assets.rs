@@ -1,8 +1,16 @@
 use crate::{AssetClass, Exchange, Identifier};
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_with::CommaSeparator;
 use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
 use uuid::Uuid;
-use vila::{Request, RequestData};
+use vila::{Client, Request, RequestData};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -20,7 +28,58 @@ impl Default for Status {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
+/// An attribute of an asset
+pub enum AssetAttribute {
+    /// A publicly traded partnership without an exception from withholding
+    PtpNoException,
+    /// A publicly traded partnership with an exception from withholding
+    PtpWithException,
+    /// An asset that is being offered in an IPO
+    Ipo,
+    /// An asset with options contracts
+    HasOptions,
+    /// An asset whose options trade until 4:15pm
+    OptionsLateClose,
+    /// An attribute not known to this crate.
+    Unknown(String),
+}
+
+impl From<String> for AssetAttribute {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "ptp_no_exception" => AssetAttribute::PtpNoException,
+            "ptp_with_exception" => AssetAttribute::PtpWithException,
+            "ipo" => AssetAttribute::Ipo,
+            "has_options" => AssetAttribute::HasOptions,
+            "options_late_close" => AssetAttribute::OptionsLateClose,
+            _ => AssetAttribute::Unknown(s),
+        }
+    }
+}
+
+impl From<AssetAttribute> for String {
+    fn from(attribute: AssetAttribute) -> Self {
+        match attribute {
+            AssetAttribute::PtpNoException => "ptp_no_exception".into(),
+            AssetAttribute::PtpWithException => "ptp_with_exception".into(),
+            AssetAttribute::Ipo => "ipo".into(),
+            AssetAttribute::HasOptions => "has_options".into(),
+            AssetAttribute::OptionsLateClose => "options_late_close".into(),
+            AssetAttribute::Unknown(s) => s,
+        }
+    }
+}
+
+impl fmt::Display for AssetAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from(self.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 /// Asset object
 pub struct Asset {
     /// Asset ID.
@@ -31,6 +90,9 @@ pub struct Asset {
     pub exchange: Exchange,
     /// Ticker of asset.
     pub symbol: String,
+    #[serde(default)]
+    /// Official name of the asset.
+    pub name: String,
     /// Whether the asset is active or inactive.
     pub status: Status,
     /// Asset is tradable on Alpaca or not.
@@ -44,6 +106,396 @@ pub struct Asset {
     pub easy_to_borrow: bool,
     /// Asset is fractionable or not.
     pub fractionable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The margin required to hold the asset, in percent.
+    pub maintenance_margin_requirement: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The smallest quantity that can be ordered, for crypto assets.
+    pub min_order_size: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The increment quantities can be ordered in, for crypto assets.
+    pub min_trade_increment: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// The increment prices can be quoted in, for crypto assets.
+    pub price_increment: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// The attributes of the asset.
+    pub attributes: Vec<AssetAttribute>,
+}
+
+impl Asset {
+    /// Whether the asset can currently be sold short at Alpaca, which requires it to be both
+    /// shortable and easy to borrow.
+    pub fn can_short(&self) -> bool {
+        self.shortable && self.easy_to_borrow
+    }
+
+    /// Whether the asset has the attribute.
+    pub fn has_attribute(&self, attribute: &AssetAttribute) -> bool {
+        self.attributes.contains(attribute)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// A filter over the fields of an asset. Fields that are not set match any asset.
+pub struct AssetFilter {
+    status: Option<Status>,
+    asset_class: Option<AssetClass>,
+    exchange: Option<Exchange>,
+    tradable: Option<bool>,
+    marginable: Option<bool>,
+    shortable: Option<bool>,
+    easy_to_borrow: Option<bool>,
+    fractionable: Option<bool>,
+    attributes: Vec<AssetAttribute>,
+}
+
+impl AssetFilter {
+    /// Create a filter that matches every asset.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match assets with the status.
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only match assets of the asset class.
+    pub fn asset_class(mut self, asset_class: AssetClass) -> Self {
+        self.asset_class = Some(asset_class);
+        self
+    }
+
+    /// Only match assets listed on the exchange.
+    pub fn exchange(mut self, exchange: Exchange) -> Self {
+        self.exchange = Some(exchange);
+        self
+    }
+
+    /// Only match assets that are, or are not, tradable.
+    pub fn tradable(mut self, tradable: bool) -> Self {
+        self.tradable = Some(tradable);
+        self
+    }
+
+    /// Only match assets that are, or are not, marginable.
+    pub fn marginable(mut self, marginable: bool) -> Self {
+        self.marginable = Some(marginable);
+        self
+    }
+
+    /// Only match assets that are, or are not, shortable.
+    pub fn shortable(mut self, shortable: bool) -> Self {
+        self.shortable = Some(shortable);
+        self
+    }
+
+    /// Only match assets that are, or are not, easy to borrow.
+    pub fn easy_to_borrow(mut self, easy_to_borrow: bool) -> Self {
+        self.easy_to_borrow = Some(easy_to_borrow);
+        self
+    }
+
+    /// Only match assets that are, or are not, fractionable.
+    pub fn fractionable(mut self, fractionable: bool) -> Self {
+        self.fractionable = Some(fractionable);
+        self
+    }
+
+    /// Only match assets with the attribute. Assets must have every attribute added.
+    pub fn attribute(mut self, attribute: AssetAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Whether the asset matches the filter.
+    pub fn matches(&self, asset: &Asset) -> bool {
+        fn check<T: PartialEq>(filter: &Option<T>, value: &T) -> bool {
+            filter.as_ref().is_none_or(|f| f == value)
+        }
+        check(&self.status, &asset.status)
+            && check(&self.asset_class, &asset.class)
+            && check(&self.exchange, &asset.exchange)
+            && check(&self.tradable, &asset.tradable)
+            && check(&self.marginable, &asset.marginable)
+            && check(&self.shortable, &asset.shortable)
+            && check(&self.easy_to_borrow, &asset.easy_to_borrow)
+            && check(&self.fractionable, &asset.fractionable)
+            && self.attributes.iter().all(|a| asset.has_attribute(a))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A change to a single asset between two snapshots of the universe.
+pub enum AssetChange {
+    /// An asset that is active in the current snapshot, but was missing or inactive in the
+    /// previous one.
+    Listed(Asset),
+    /// An asset that was active in the previous snapshot, but is missing or inactive in the
+    /// current one.
+    Delisted(Asset),
+    /// An asset that could not be sold short in the previous snapshot, but can in the current one.
+    BecameShortable(Asset),
+    /// An asset that could be sold short in the previous snapshot, but cannot in the current one.
+    BecameUnshortable(Asset),
+}
+
+#[derive(Debug)]
+/// An error loading an asset universe.
+pub enum UniverseError {
+    /// Reading or writing the cache failed.
+    Io(io::Error),
+    /// Fetching the assets failed.
+    Request(vila::Error),
+}
+
+impl fmt::Display for UniverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniverseError::Io(e) => write!(f, "asset cache failed: {}", e),
+            UniverseError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UniverseError {}
+
+#[derive(Serialize, Deserialize)]
+struct CachedUniverse {
+    fetched_at: DateTime<Utc>,
+    assets: Vec<Asset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "CachedUniverse", into = "CachedUniverse")]
+/// A snapshot of the assets available at Alpaca, indexed by symbol and asset ID, so they can be
+/// queried locally instead of requesting the full list each time.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     assets::{AssetFilter, AssetUniverse, GetAssets},
+///     paper_client, Exchange,
+/// };
+/// use chrono::Duration;
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let universe =
+///         AssetUniverse::load_or_fetch(&client, &GetAssets::new(), "assets.json", Duration::days(1))
+///             .await?;
+///     let filter = AssetFilter::new()
+///         .exchange(Exchange::Nasdaq)
+///         .tradable(true)
+///         .shortable(true)
+///         .easy_to_borrow(true);
+///     for asset in universe.filter(&filter) {
+///         println!("{}", asset.symbol);
+///     }
+///     let fresh = AssetUniverse::fetch(&client, &GetAssets::new()).await?;
+///     for change in universe.diff(&fresh) {
+///         println!("{:?}", change);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct AssetUniverse {
+    fetched_at: DateTime<Utc>,
+    assets: Vec<Asset>,
+    ids: HashMap<Uuid, usize>,
+}
+
+impl From<CachedUniverse> for AssetUniverse {
+    fn from(cached: CachedUniverse) -> Self {
+        Self::with_fetched_at(cached.assets, cached.fetched_at)
+    }
+}
+
+impl From<AssetUniverse> for CachedUniverse {
+    fn from(universe: AssetUniverse) -> Self {
+        Self {
+            fetched_at: universe.fetched_at,
+            assets: universe.assets,
+        }
+    }
+}
+
+impl AssetUniverse {
+    /// Create a universe of the assets, fetched now.
+    pub fn new(assets: Vec<Asset>) -> Self {
+        Self::with_fetched_at(assets, Utc::now())
+    }
+
+    /// Create a universe of the assets, fetched at the given time.
+    pub fn with_fetched_at(mut assets: Vec<Asset>, fetched_at: DateTime<Utc>) -> Self {
+        // Active assets sort first so that they take precedence over inactive ones with the
+        // same symbol.
+        assets.sort_by(|a, b| {
+            (&a.symbol, a.status != Status::Active).cmp(&(&b.symbol, b.status != Status::Active))
+        });
+        let ids = assets.iter().enumerate().map(|(i, a)| (a.id, i)).collect();
+        Self {
+            fetched_at,
+            assets,
+            ids,
+        }
+    }
+
+    /// Fetch the assets.
+    pub async fn fetch(client: &Client, request: &GetAssets) -> Result<Self, vila::Error> {
+        client.send(request).await.map(Self::new)
+    }
+
+    /// Load a universe saved with `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Save the universe as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer(BufWriter::new(file), self)?)
+    }
+
+    /// Load the universe from the cache at `path` if it can be read and was fetched less than
+    /// `ttl` ago, otherwise fetch it and save it to the cache. A missing or unreadable cache is
+    /// overwritten.
+    pub async fn load_or_fetch<P: AsRef<Path>>(
+        client: &Client,
+        request: &GetAssets,
+        path: P,
+        ttl: Duration,
+    ) -> Result<Self, UniverseError> {
+        if let Ok(cached) = Self::load(&path) {
+            if !cached.is_stale(ttl) {
+                return Ok(cached);
+            }
+        }
+        let universe = Self::fetch(client, request)
+            .await
+            .map_err(UniverseError::Request)?;
+        universe.save(path).map_err(UniverseError::Io)?;
+        Ok(universe)
+    }
+
+    /// When the assets were fetched.
+    pub fn fetched_at(&self) -> DateTime<Utc> {
+        self.fetched_at
+    }
+
+    /// Whether the assets were fetched more than `ttl` ago.
+    pub fn is_stale(&self, ttl: Duration) -> bool {
+        self.fetched_at + ttl <= Utc::now()
+    }
+
+    /// Get the asset with the given ID.
+    pub fn get(&self, id: &Uuid) -> Option<&Asset> {
+        self.ids.get(id).map(|&i| &self.assets[i])
+    }
+
+    /// Get the asset with the given symbol, preferring an active asset if there are several.
+    pub fn get_symbol(&self, symbol: &str) -> Option<&Asset> {
+        let i = self.assets.partition_point(|a| a.symbol.as_str() < symbol);
+        self.assets.get(i).filter(|a| a.symbol == symbol)
+    }
+
+    /// Iterate over the assets, ordered by symbol.
+    pub fn iter(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.iter()
+    }
+
+    /// The number of assets in the universe.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Whether the universe contains no assets.
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// The assets that match the filter, ordered by symbol.
+    pub fn filter<'a>(&'a self, filter: &'a AssetFilter) -> impl Iterator<Item = &'a Asset> {
+        self.assets.iter().filter(move |a| filter.matches(a))
+    }
+
+    /// The assets whose symbol starts with the prefix, ordered by symbol.
+    pub fn search_prefix<'a>(&'a self, prefix: &str) -> impl Iterator<Item = &'a Asset> {
+        let prefix = prefix.to_uppercase();
+        let start = self.assets.partition_point(|a| a.symbol < prefix);
+        self.assets[start..]
+            .iter()
+            .take_while(move |a| a.symbol.starts_with(&prefix))
+    }
+
+    /// Up to `limit` assets whose symbol is close to the query, closest first. Closeness is the
+    /// edit distance between the symbols, and symbols more than a third of the query's length
+    /// away, or one edit for short queries, are not included.
+    pub fn search_fuzzy(&self, query: &str, limit: usize) -> Vec<&Asset> {
+        let query = query.to_uppercase();
+        let max_distance = (query.chars().count() / 3).max(1);
+        let mut matches: Vec<(usize, &Asset)> = self
+            .assets
+            .iter()
+            .map(|a| (edit_distance(&query, &a.symbol), a))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        matches.sort_by(|a, b| (a.0, &a.1.symbol).cmp(&(b.0, &b.1.symbol)));
+        matches.into_iter().take(limit).map(|(_, a)| a).collect()
+    }
+
+    /// List the changes between this snapshot and `current`, ordered by symbol.
+    pub fn diff(&self, current: &AssetUniverse) -> Vec<AssetChange> {
+        let symbols: BTreeSet<(&str, &Uuid)> = self
+            .assets
+            .iter()
+            .chain(current.assets.iter())
+            .map(|a| (a.symbol.as_str(), &a.id))
+            .collect();
+        // A renamed asset appears under both symbols, but is only compared once.
+        let mut seen = BTreeSet::new();
+        let active = |a: &Asset| a.status == Status::Active;
+        symbols
+            .into_iter()
+            .filter(|(_, id)| seen.insert(*id))
+            .filter_map(|(_, id)| match (self.get(id), current.get(id)) {
+                (previous, Some(current)) if active(current) && !previous.is_some_and(active) => {
+                    Some(AssetChange::Listed(current.clone()))
+                }
+                (Some(previous), None) if active(previous) => {
+                    Some(AssetChange::Delisted(previous.clone()))
+                }
+                (Some(previous), Some(current)) if active(previous) && !active(current) => {
+                    Some(AssetChange::Delisted(current.clone()))
+                }
+                (Some(previous), Some(current)) if !previous.can_short() && current.can_short() => {
+                    Some(AssetChange::BecameShortable(current.clone()))
+                }
+                (Some(previous), Some(current)) if previous.can_short() && !current.can_short() => {
+                    Some(AssetChange::BecameUnshortable(current.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -67,9 +519,17 @@ pub struct Asset {
 ///         .await?;
 ///     Ok(())
 /// }
+/// ```
 pub struct GetAssets {
     status: Status,
     asset_class: AssetClass,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exchange: Option<Exchange>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        with = "serde_with::rust::StringWithSeparator::<CommaSeparator>"
+    )]
+    attributes: Vec<AssetAttribute>,
 }
 
 impl GetAssets {
@@ -89,6 +549,18 @@ impl GetAssets {
         self.asset_class = asset_class;
         self
     }
+
+    /// Set the Exchange used to filter assets.
+    pub fn exchange(mut self, exchange: Exchange) -> Self {
+        self.exchange = Some(exchange);
+        self
+    }
+
+    /// Add an attribute used to filter assets. Assets with any of the attributes are returned.
+    pub fn add_attribute(mut self, attribute: AssetAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
 }
 
 impl Default for GetAssets {
@@ -96,6 +568,8 @@ impl Default for GetAssets {
         Self {
             status: Status::Active,
             asset_class: AssetClass::UsEquity,
+            exchange: None,
+            attributes: Vec::new(),
         }
     }
 }
@@ -120,14 +594,16 @@ impl Request for GetAssets {
 /// ```no_run
 /// use apca_rest::{
 ///     assets::{Asset, GetAsset},
-///     paper_client,
+///     paper_client, Identifier, Symbol,
 /// };
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
-///     let asset: Asset = client.send(&GetAsset::new("AAPL")).await?;
+///     let asset: Asset = client.send(&GetAsset::new("AAPL".parse::<Symbol>()?)).await?;
+///     let asset: Asset = client.send(&GetAsset::new("BTC/USD:FTXU:crypto".parse::<Identifier>()?)).await?;
 ///     Ok(())
 /// }
+/// ```
 pub struct GetAsset {
     identifier: Identifier,
 }
@@ -146,15 +622,17 @@ impl Request for GetAsset {
     type Response = Asset;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/assets/{}", self.identifier).into()
+        format!("/v2/assets/{}", self.identifier.url_encoded()).into()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::client_with_url;
+    use crate::test_fixtures::{asset, ASSET};
+    use crate::{client_with_url, Symbol};
     use mockito::{mock, Matcher};
+    use serde_json::json;
 
     #[tokio::test]
     async fn get_assets() {
@@ -173,10 +651,57 @@ mod test {
         let req = GetAssets {
             status: Status::Active,
             asset_class: AssetClass::UsEquity,
+            exchange: None,
+            attributes: Vec::new(),
         };
         client.send(&req).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn get_assets_filtered() {
+        let _m = mock("GET", "/v2/assets")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("status".into(), "active".into()),
+                Matcher::UrlEncoded("asset_class".into(), "crypto".into()),
+                Matcher::UrlEncoded("exchange".into(), "FTXU".into()),
+                Matcher::UrlEncoded("attributes".into(), "ptp_no_exception,has_options".into()),
+            ]))
+            .with_body(format!("[{}]", CRYPTO_ASSET))
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+        let req = GetAssets::new()
+            .asset_class(AssetClass::Crypto)
+            .exchange(Exchange::Ftxu)
+            .add_attribute(AssetAttribute::PtpNoException)
+            .add_attribute(AssetAttribute::HasOptions);
+        let assets = client.send(&req).await.unwrap();
+        assert_eq!(assets[0].exchange, Exchange::Other("XCRY".into()));
+        assert_eq!(assets[0].min_trade_increment, Some(Decimal::new(1, 4)));
+        assert_eq!(assets[0].price_increment, Some(Decimal::new(1, 0)));
+    }
+
+    #[test]
+    fn asset_model() {
+        let asset: Asset = serde_json::from_str(ASSET).unwrap();
+        assert_eq!(asset.name, "Apple Inc. Common Stock");
+        assert_eq!(
+            asset.maintenance_margin_requirement,
+            Some(Decimal::new(30, 0))
+        );
+        assert_eq!(asset.min_order_size, None);
+        assert_eq!(
+            asset.attributes,
+            vec![
+                AssetAttribute::HasOptions,
+                AssetAttribute::Unknown("fractional_eh_enabled".into())
+            ]
+        );
+        let filter = AssetFilter::new().attribute(AssetAttribute::HasOptions);
+        assert!(filter.matches(&asset));
+        assert!(!filter.attribute(AssetAttribute::Ipo).matches(&asset));
+    }
+
     #[tokio::test]
     async fn get_asset_by_ticker() {
         let _m = mock("GET", "/v2/assets/AAPL")
@@ -187,7 +712,25 @@ mod test {
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        client.send(&GetAsset::new("AAPL")).await.unwrap();
+        client
+            .send(&GetAsset::new("AAPL".parse::<Symbol>().unwrap()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn get_asset_by_crypto_pair() {
+        let _m = mock("GET", "/v2/assets/BTC%2FUSD")
+            .with_body(CRYPTO_ASSET)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let asset = client
+            .send(&GetAsset::new("btc/usd".parse::<Symbol>().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(asset.symbol, "BTC/USD");
     }
 
     #[tokio::test]
@@ -208,16 +751,158 @@ mod test {
             .unwrap();
     }
 
-    const ASSET: &'static str = r#"{
-           "id": "904837e3-3b76-47ec-b432-046db621571b",
-  		   "class": "us_equity",
-  		   "exchange": "NASDAQ",
-  		   "symbol": "AAPL",
-  		   "status": "active",
-  		   "tradable": true,
-  		   "marginable": true,
-  		   "shortable": true,
-  		   "easy_to_borrow": true,
-           "fractionable": true
+    fn symbols<'a, T: IntoIterator<Item = &'a Asset>>(assets: T) -> Vec<&'a str> {
+        assets.into_iter().map(|a| a.symbol.as_str()).collect()
+    }
+
+    fn universe() -> AssetUniverse {
+        AssetUniverse::new(vec![
+            asset(json!({"symbol": "MSFT", "id": Uuid::from_u128(1)})),
+            asset(json!({"symbol": "AAPL", "id": Uuid::from_u128(4), "status": "inactive"})),
+            asset(json!({"symbol": "AAPL", "id": Uuid::from_u128(2)})),
+            asset(json!({
+                "symbol": "AMC",
+                "id": Uuid::from_u128(3),
+                "exchange": "NYSE",
+                "easy_to_borrow": false,
+            })),
+            asset(json!({"symbol": "AAP", "id": Uuid::from_u128(5), "exchange": "NYSE"})),
+        ])
+    }
+
+    #[test]
+    fn universe_lookup() {
+        let universe = universe();
+        assert_eq!(
+            symbols(universe.iter()),
+            vec!["AAP", "AAPL", "AAPL", "AMC", "MSFT"]
+        );
+        assert_eq!(universe.get_symbol("AAPL").unwrap().id, Uuid::from_u128(2));
+        assert_eq!(universe.get(&Uuid::from_u128(4)).unwrap().symbol, "AAPL");
+        assert!(universe.get_symbol("AA").is_none());
+
+        let filter = AssetFilter::new()
+            .status(Status::Active)
+            .exchange(Exchange::Nasdaq)
+            .shortable(true)
+            .easy_to_borrow(true);
+        assert_eq!(symbols(universe.filter(&filter)), vec!["AAPL", "MSFT"]);
+        assert_eq!(
+            symbols(universe.filter(&AssetFilter::new().easy_to_borrow(false))),
+            vec!["AMC"]
+        );
+    }
+
+    #[test]
+    fn universe_search() {
+        let universe = universe();
+        assert_eq!(
+            symbols(universe.search_prefix("aa")),
+            vec!["AAP", "AAPL", "AAPL"]
+        );
+        assert_eq!(symbols(universe.search_prefix("B")), Vec::<&str>::new());
+        assert_eq!(
+            symbols(universe.search_fuzzy("APPL", 2)),
+            vec!["AAPL", "AAPL"]
+        );
+        assert_eq!(symbols(universe.search_fuzzy("MSFY", 5)), vec!["MSFT"]);
+        assert_eq!(edit_distance("APPL", "AAPL"), 1);
+        assert_eq!(edit_distance("", "AMC"), 3);
+    }
+
+    #[test]
+    fn universe_diff() {
+        let previous = universe();
+        let mut assets: Vec<Asset> = previous.iter().cloned().collect();
+        assets.retain(|a| a.symbol != "MSFT");
+        for asset in assets.iter_mut() {
+            match asset.symbol.as_str() {
+                "AMC" => asset.easy_to_borrow = true,
+                "AAP" => asset.shortable = false,
+                _ => {}
+            }
+        }
+        assets.push(asset(json!({"symbol": "TSLA", "id": Uuid::from_u128(6)})));
+        let current = AssetUniverse::new(assets);
+
+        let changes: Vec<_> = previous
+            .diff(&current)
+            .into_iter()
+            .map(|change| match change {
+                AssetChange::Listed(a) => format!("listed {}", a.symbol),
+                AssetChange::Delisted(a) => format!("delisted {}", a.symbol),
+                AssetChange::BecameShortable(a) => format!("shortable {}", a.symbol),
+                AssetChange::BecameUnshortable(a) => format!("unshortable {}", a.symbol),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "unshortable AAP",
+                "shortable AMC",
+                "delisted MSFT",
+                "listed TSLA"
+            ]
+        );
+    }
+
+    #[test]
+    fn universe_diff_renamed() {
+        let previous = AssetUniverse::new(vec![asset(json!({"symbol": "FB"}))]);
+        let current =
+            AssetUniverse::new(vec![asset(json!({"symbol": "META", "shortable": false}))]);
+        let renamed = current.get_symbol("META").unwrap().clone();
+        assert_eq!(
+            previous.diff(&current),
+            vec![AssetChange::BecameUnshortable(renamed)]
+        );
+    }
+
+    #[tokio::test]
+    async fn universe_cache() {
+        let _m = mock("GET", "/v2/assets")
+            .match_query(Matcher::Any)
+            .with_body(format!("[{}]", ASSET))
+            .expect(2)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+        let path = std::env::temp_dir().join(format!("apca-assets-{}.json", std::process::id()));
+        // A corrupt cache is refetched and overwritten.
+        std::fs::write(&path, "[{\"truncated").unwrap();
+
+        let request = GetAssets::new();
+        let fetched = AssetUniverse::load_or_fetch(&client, &request, &path, Duration::hours(1))
+            .await
+            .unwrap();
+        let cached = AssetUniverse::load_or_fetch(&client, &request, &path, Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(fetched, cached);
+        let refetched = AssetUniverse::load_or_fetch(&client, &request, &path, Duration::zero())
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        _m.assert();
+        assert!(refetched.fetched_at() > cached.fetched_at());
+        assert_eq!(refetched.get_symbol("AAPL"), cached.get_symbol("AAPL"));
+    }
+
+    const CRYPTO_ASSET: &'static str = r#"{
+           "id": "64bbff51-59d6-4b3c-9351-13ad85e3c752",
+           "class": "crypto",
+           "exchange": "XCRY",
+           "symbol": "BTC/USD",
+           "name": "Bitcoin  / US Dollar",
+           "status": "active",
+           "tradable": true,
+           "marginable": false,
+           "maintenance_margin_requirement": 100,
+           "shortable": false,
+           "easy_to_borrow": false,
+           "fractionable": true,
+           "min_order_size": "0.0001",
+           "min_trade_increment": "0.0001",
+           "price_increment": "1"
         }"#;
 }
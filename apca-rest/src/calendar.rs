@@ -1,8 +1,13 @@
-use crate::utils::{hm_from_str, hm_to_string};
-use chrono::{NaiveDate, NaiveTime};
+use crate::utils::{hhmm_from_str_optional, hhmm_to_string_optional, hm_from_str, hm_to_string};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::{America::New_York, Tz};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use vila::{Request, RequestData};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use vila::{Client, Request, RequestData};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 /// Calendar object
@@ -15,6 +20,65 @@ pub struct Calendar {
     #[serde(deserialize_with = "hm_from_str", serialize_with = "hm_to_string")]
     /// The time the market closes at on this date.
     pub close: NaiveTime,
+    #[serde(
+        default,
+        deserialize_with = "hhmm_from_str_optional",
+        serialize_with = "hhmm_to_string_optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// The time the pre-market session opens at on this date.
+    pub session_open: Option<NaiveTime>,
+    #[serde(
+        default,
+        deserialize_with = "hhmm_from_str_optional",
+        serialize_with = "hhmm_to_string_optional",
+        skip_serializing_if = "Option::is_none"
+    )]
+    /// The time the post-market session closes at on this date.
+    pub session_close: Option<NaiveTime>,
+}
+
+impl Calendar {
+    /// The time the market opens, in New York time.
+    pub fn market_open(&self) -> DateTime<Tz> {
+        new_york(self.date, self.open)
+    }
+
+    /// The time the market closes, in New York time.
+    pub fn market_close(&self) -> DateTime<Tz> {
+        new_york(self.date, self.close)
+    }
+
+    /// The pre-market session, from the session open to the market open.
+    pub fn pre_market(&self) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        self.session_open
+            .map(|open| (new_york(self.date, open), self.market_open()))
+    }
+
+    /// The post-market session, from the market close to the session close.
+    pub fn post_market(&self) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        self.session_close
+            .map(|close| (self.market_close(), new_york(self.date, close)))
+    }
+
+    /// Whether the market closes before the regular close of 4pm.
+    pub fn is_early_close(&self) -> bool {
+        self.close < NaiveTime::from_hms_opt(16, 0, 0).unwrap()
+    }
+}
+
+fn new_york(date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    let local = date.and_time(time);
+    New_York
+        .from_local_datetime(&local)
+        .earliest()
+        // Times skipped by the start of daylight saving time are taken to be an hour later.
+        .or_else(|| {
+            New_York
+                .from_local_datetime(&(local + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap()
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -33,12 +97,13 @@ pub struct Calendar {
 ///     let cal: Vec<Calendar> = client
 ///         .send(
 ///             &GetCalendar::new()
-///                 .start(NaiveDate::from_ymd(2020, 1, 1))
-///                 .end(NaiveDate::from_ymd(2020, 12, 31)),
+///                 .start(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap())
+///                 .end(NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()),
 ///         )
 ///         .await?;
 ///     Ok(())
 /// }
+/// ```
 pub struct GetCalendar {
     start: NaiveDate,
     end: NaiveDate,
@@ -66,8 +131,8 @@ impl GetCalendar {
 impl Default for GetCalendar {
     fn default() -> Self {
         Self {
-            start: NaiveDate::from_ymd(1970, 1, 1),
-            end: NaiveDate::from_ymd(2029, 12, 31),
+            start: NaiveDate::from_ymd_opt(1970, 1, 1).unwrap(),
+            end: NaiveDate::from_ymd_opt(2029, 12, 31).unwrap(),
         }
     }
 }
@@ -85,10 +150,250 @@ impl Request for GetCalendar {
     }
 }
 
+#[derive(Debug)]
+/// An error loading a trading calendar.
+pub enum CalendarError {
+    /// Reading or writing the cache failed.
+    Io(io::Error),
+    /// Fetching the calendar failed.
+    Request(vila::Error),
+}
+
+impl fmt::Display for CalendarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalendarError::Io(e) => write!(f, "calendar cache failed: {}", e),
+            CalendarError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CalendarError {}
+
+#[derive(Serialize, Deserialize)]
+struct CachedCalendar {
+    fetched_at: DateTime<Utc>,
+    start: NaiveDate,
+    end: NaiveDate,
+    days: Vec<Calendar>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(from = "CachedCalendar", into = "CachedCalendar")]
+/// The market calendar, loaded once and queried locally. Times are in New York time.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     calendar::{GetCalendar, TradingCalendar},
+///     paper_client,
+/// };
+/// use chrono::{Duration, NaiveDate};
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let calendar = TradingCalendar::load_or_fetch(
+///         &client,
+///         &GetCalendar::new(),
+///         "calendar.json",
+///         Duration::days(7),
+///     )
+///     .await?;
+///     let today = NaiveDate::from_ymd_opt(2021, 11, 26).unwrap();
+///     if let Some((open, close)) = calendar.session_bounds(today) {
+///         println!("{} to {}, early close: {}", open, close, calendar.is_early_close(today));
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct TradingCalendar {
+    fetched_at: DateTime<Utc>,
+    start: NaiveDate,
+    end: NaiveDate,
+    days: Vec<Calendar>,
+}
+
+impl From<Vec<Calendar>> for TradingCalendar {
+    /// Create a calendar of the days, covering the range from the first to the last day.
+    fn from(days: Vec<Calendar>) -> Self {
+        let start = days.iter().map(|day| day.date).min().unwrap_or_default();
+        let end = days.iter().map(|day| day.date).max().unwrap_or_default();
+        Self::with_range(days, start, end, Utc::now())
+    }
+}
+
+impl From<CachedCalendar> for TradingCalendar {
+    fn from(cached: CachedCalendar) -> Self {
+        Self::with_range(cached.days, cached.start, cached.end, cached.fetched_at)
+    }
+}
+
+impl From<TradingCalendar> for CachedCalendar {
+    fn from(calendar: TradingCalendar) -> Self {
+        Self {
+            fetched_at: calendar.fetched_at,
+            start: calendar.start,
+            end: calendar.end,
+            days: calendar.days,
+        }
+    }
+}
+
+impl From<TradingCalendar> for Vec<Calendar> {
+    fn from(calendar: TradingCalendar) -> Self {
+        calendar.days
+    }
+}
+
+impl TradingCalendar {
+    /// Create a calendar of the days, covering the range from `start` to `end`, fetched at the
+    /// given time.
+    pub fn with_range(
+        mut days: Vec<Calendar>,
+        start: NaiveDate,
+        end: NaiveDate,
+        fetched_at: DateTime<Utc>,
+    ) -> Self {
+        days.sort_by_key(|day| day.date);
+        days.dedup_by_key(|day| day.date);
+        Self {
+            fetched_at,
+            start,
+            end,
+            days,
+        }
+    }
+
+    /// Fetch the calendar.
+    pub async fn fetch(client: &Client, request: &GetCalendar) -> Result<Self, vila::Error> {
+        let days = client.send(request).await?;
+        Ok(Self::with_range(
+            days,
+            request.start,
+            request.end,
+            Utc::now(),
+        ))
+    }
+
+    /// Load a calendar saved with `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Save the calendar as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        Ok(serde_json::to_writer(BufWriter::new(file), self)?)
+    }
+
+    /// Load the calendar from the cache at `path` if it can be read, was fetched less than `ttl`
+    /// ago and covers the range of the request, otherwise fetch it and save it to the cache. A
+    /// missing or unreadable cache is overwritten.
+    pub async fn load_or_fetch<P: AsRef<Path>>(
+        client: &Client,
+        request: &GetCalendar,
+        path: P,
+        ttl: Duration,
+    ) -> Result<Self, CalendarError> {
+        if let Ok(cached) = Self::load(&path) {
+            if !cached.is_stale(ttl) && cached.covers(request) {
+                return Ok(cached);
+            }
+        }
+        let calendar = Self::fetch(client, request)
+            .await
+            .map_err(CalendarError::Request)?;
+        calendar.save(path).map_err(CalendarError::Io)?;
+        Ok(calendar)
+    }
+
+    /// When the calendar was fetched.
+    pub fn fetched_at(&self) -> DateTime<Utc> {
+        self.fetched_at
+    }
+
+    /// Whether the calendar was fetched more than `ttl` ago.
+    pub fn is_stale(&self, ttl: Duration) -> bool {
+        self.fetched_at + ttl <= Utc::now()
+    }
+
+    /// Whether the calendar covers the whole range of the request.
+    pub fn covers(&self, request: &GetCalendar) -> bool {
+        self.start <= request.start && request.end <= self.end
+    }
+
+    /// The trading days, in order.
+    pub fn days(&self) -> &[Calendar] {
+        &self.days
+    }
+
+    /// The calendar for the date, if it is a trading day.
+    pub fn get(&self, date: NaiveDate) -> Option<&Calendar> {
+        self.days
+            .binary_search_by_key(&date, |day| day.date)
+            .ok()
+            .map(|i| &self.days[i])
+    }
+
+    /// Whether the market is open on the date.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        self.get(date).is_some()
+    }
+
+    /// The first trading day after the date.
+    pub fn next_trading_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let i = self.days.partition_point(|day| day.date <= date);
+        self.days.get(i).map(|day| day.date)
+    }
+
+    /// The last trading day before the date.
+    pub fn previous_trading_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let i = self.days.partition_point(|day| day.date < date);
+        i.checked_sub(1).map(|i| self.days[i].date)
+    }
+
+    /// The trading days from `start` to `end`, both inclusive.
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> &[Calendar] {
+        let from = self.days.partition_point(|day| day.date < start);
+        let to = self.days.partition_point(|day| day.date <= end);
+        &self.days[from..to.max(from)]
+    }
+
+    /// The market open and close on the date, if it is a trading day.
+    pub fn session_bounds(&self, date: NaiveDate) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        self.get(date)
+            .map(|day| (day.market_open(), day.market_close()))
+    }
+
+    /// The pre-market session on the date, if it is a trading day and the session is known.
+    pub fn pre_market(&self, date: NaiveDate) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        self.get(date).and_then(Calendar::pre_market)
+    }
+
+    /// The post-market session on the date, if it is a trading day and the session is known.
+    pub fn post_market(&self, date: NaiveDate) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        self.get(date).and_then(Calendar::post_market)
+    }
+
+    /// Whether the date is a trading day on which the market closes early.
+    pub fn is_early_close(&self, date: NaiveDate) -> bool {
+        self.get(date)
+            .map(Calendar::is_early_close)
+            .unwrap_or(false)
+    }
+
+    /// The trading days on which the market closes early.
+    pub fn early_closes(&self) -> impl Iterator<Item = &Calendar> {
+        self.days.iter().filter(|day| day.is_early_close())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::client_with_url;
+    use crate::test_fixtures::{calendar, CALENDAR};
     use mockito::{mock, Matcher};
 
     #[tokio::test]
@@ -115,4 +420,118 @@ mod test {
 
         client.send(&GetCalendar::new()).await.unwrap();
     }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn load_or_fetch() {
+        let _m = mock("GET", "/v2/calendar")
+            .match_query(Matcher::Any)
+            .with_body(CALENDAR)
+            .expect(3)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+        let path = std::env::temp_dir().join(format!("apca-calendar-{}.json", std::process::id()));
+        // A cache in an old format is refetched and overwritten.
+        std::fs::write(&path, CALENDAR).unwrap();
+
+        let request = GetCalendar::new()
+            .start(date("2021-11-01"))
+            .end(date("2021-11-30"));
+        let fetched = TradingCalendar::load_or_fetch(&client, &request, &path, Duration::hours(1))
+            .await
+            .unwrap();
+        let narrower = GetCalendar::new()
+            .start(date("2021-11-20"))
+            .end(date("2021-11-30"));
+        let cached = TradingCalendar::load_or_fetch(&client, &narrower, &path, Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(fetched, cached);
+        assert_eq!(cached.days().len(), 3);
+
+        let stale = TradingCalendar::load_or_fetch(&client, &request, &path, Duration::zero())
+            .await
+            .unwrap();
+        assert!(stale.fetched_at() > cached.fetched_at());
+        let wider = GetCalendar::new()
+            .start(date("2021-11-01"))
+            .end(date("2021-12-31"));
+        let refetched = TradingCalendar::load_or_fetch(&client, &wider, &path, Duration::hours(1))
+            .await
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        _m.assert();
+        assert!(refetched.covers(&wider));
+        assert!(!cached.covers(&wider));
+    }
+
+    #[test]
+    fn trading_days() {
+        let calendar = calendar();
+        assert!(calendar.is_trading_day(date("2021-11-24")));
+        assert!(!calendar.is_trading_day(date("2021-11-25")));
+        assert_eq!(
+            calendar.next_trading_day(date("2021-11-24")),
+            Some(date("2021-11-26"))
+        );
+        assert_eq!(
+            calendar.next_trading_day(date("2021-11-25")),
+            Some(date("2021-11-26"))
+        );
+        assert_eq!(calendar.next_trading_day(date("2021-11-29")), None);
+        assert_eq!(
+            calendar.previous_trading_day(date("2021-11-29")),
+            Some(date("2021-11-26"))
+        );
+        assert_eq!(calendar.previous_trading_day(date("2021-11-24")), None);
+        let between: Vec<_> = calendar
+            .trading_days_between(date("2021-11-25"), date("2021-11-29"))
+            .iter()
+            .map(|day| day.date)
+            .collect();
+        assert_eq!(between, vec![date("2021-11-26"), date("2021-11-29")]);
+        assert!(calendar
+            .trading_days_between(date("2021-11-29"), date("2021-11-24"))
+            .is_empty());
+    }
+
+    #[test]
+    fn sessions() {
+        let calendar = calendar();
+        let (open, close) = calendar.session_bounds(date("2021-11-26")).unwrap();
+        assert_eq!(open.to_rfc3339(), "2021-11-26T09:30:00-05:00");
+        assert_eq!(close.to_rfc3339(), "2021-11-26T13:00:00-05:00");
+        assert!(calendar.is_early_close(date("2021-11-26")));
+        assert!(!calendar.is_early_close(date("2021-11-29")));
+        assert_eq!(calendar.early_closes().count(), 1);
+
+        let (pre_open, _) = calendar.pre_market(date("2021-11-26")).unwrap();
+        let (_, post_close) = calendar.post_market(date("2021-11-26")).unwrap();
+        assert_eq!(pre_open.to_rfc3339(), "2021-11-26T04:00:00-05:00");
+        assert_eq!(post_close.to_rfc3339(), "2021-11-26T17:00:00-05:00");
+        assert_eq!(calendar.session_bounds(date("2021-11-25")), None);
+
+        let serialized = serde_json::to_value(&calendar).unwrap();
+        assert_eq!(serialized["days"][0]["session_open"], "0400");
+        assert_eq!(serialized["days"][0]["open"], "09:30");
+        assert_eq!(serialized["start"], "2021-11-24");
+        assert_eq!(serialized["end"], "2021-11-29");
+        let roundtrip: TradingCalendar = serde_json::from_value(serialized).unwrap();
+        assert_eq!(roundtrip, calendar);
+    }
+
+    #[test]
+    fn skipped_local_time() {
+        let time = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        let instant = new_york(date("2021-03-14"), time);
+        assert_eq!(instant.to_rfc3339(), "2021-03-14T03:30:00-04:00");
+        assert_eq!(
+            instant.with_timezone(&chrono::Utc).to_rfc3339(),
+            "2021-03-14T07:30:00+00:00"
+        );
+    }
 }
@@ -0,0 +1,240 @@
+use crate::account_activities::{Activity, ActivityType, GetAccountActivities};
+use crate::portfolio_history::PortfolioHistory;
+use crate::Sort;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::America::New_York;
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use vila::Client;
+
+/// The activity types that move cash into or out of the account.
+pub const CASH_FLOW_ACTIVITY_TYPES: &[ActivityType] = &[
+    ActivityType::CashDeposit,
+    ActivityType::CashWithdrawal,
+    ActivityType::JournalEntryCash,
+    ActivityType::AcatsCash,
+];
+
+#[derive(Clone, Debug, PartialEq)]
+/// A cash flow into or out of the account.
+pub struct CashFlow {
+    /// The id of the activity
+    pub id: String,
+    /// The date of the flow
+    pub date: NaiveDate,
+    /// The type of the activity
+    pub activity_type: ActivityType,
+    /// The amount of the flow, positive into the account and negative out of it
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug, Default)]
+/// The external cash flows of the account, used to separate investment returns from deposits and
+/// withdrawals.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     cash_flows::CashFlows,
+///     paper_client,
+///     portfolio_history::{GetPortfolioHistory, Period, PortfolioHistory, Timeframe},
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let flows = CashFlows::fetch(&client, &CashFlows::request()).await?;
+///     let history: PortfolioHistory = client
+///         .send(
+///             &GetPortfolioHistory::new()
+///                 .period(Period::years(1))
+///                 .timeframe(Timeframe::OneDay),
+///         )
+///         .await?;
+///     println!("{:?}", flows.time_weighted_return(&history));
+///     Ok(())
+/// }
+/// ```
+pub struct CashFlows {
+    flows: Vec<CashFlow>,
+}
+
+impl CashFlows {
+    /// Create an empty set of cash flows.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A request for all the cash flow activity types, oldest first. Date filters can be added to
+    /// the request before it is passed to `fetch`.
+    pub fn request() -> GetAccountActivities {
+        GetAccountActivities::new()
+            .add_activities(CASH_FLOW_ACTIVITY_TYPES)
+            .sort(Sort::Ascending)
+    }
+
+    /// Fetch every page of the request and record the cash flows.
+    pub async fn fetch(
+        client: &Client,
+        request: &GetAccountActivities,
+    ) -> Result<Self, vila::Error> {
+        let pages: Vec<Vec<Activity>> = client.send_paginated(request).try_collect().await?;
+        Ok(Self::new().record(pages.iter().flatten()))
+    }
+
+    /// Record the cash flow activities. Other activities are ignored.
+    pub fn record<'a, T: IntoIterator<Item = &'a Activity>>(mut self, activities: T) -> Self {
+        for activity in activities {
+            if let Activity::NonTradeActivity(activity) = activity {
+                if CASH_FLOW_ACTIVITY_TYPES.contains(&activity.activity_type) {
+                    self.flows.push(CashFlow {
+                        id: activity.id.clone(),
+                        date: activity.date,
+                        activity_type: activity.activity_type.clone(),
+                        amount: activity.net_amount,
+                    });
+                }
+            }
+        }
+        self.flows.sort_by_key(|f| f.date);
+        self
+    }
+
+    /// The recorded flows, oldest first.
+    pub fn flows(&self) -> &[CashFlow] {
+        &self.flows
+    }
+
+    /// The net flow on each date with flows.
+    pub fn by_date(&self) -> BTreeMap<NaiveDate, Decimal> {
+        let mut by_date = BTreeMap::new();
+        for flow in &self.flows {
+            *by_date.entry(flow.date).or_default() += flow.amount;
+        }
+        by_date
+    }
+
+    /// The net flow after `start` and up to and including `end`.
+    pub fn net_between(&self, start: NaiveDate, end: NaiveDate) -> Decimal {
+        self.flows
+            .iter()
+            .filter(|f| f.date > start && f.date <= end)
+            .map(|f| f.amount)
+            .sum()
+    }
+
+    /// The return of each period of the history, excluding the effect of cash flows. Flows are
+    /// assumed to happen at the end of the period their date falls in, so the return of a period is
+    /// its closing equity less its flows, over its opening equity. Elements without equity are
+    /// skipped, as are periods that start with no equity, such as those before the account was
+    /// funded.
+    pub fn flow_adjusted_returns(
+        &self,
+        history: &PortfolioHistory,
+    ) -> Vec<(DateTime<Utc>, Decimal)> {
+        let equity: Vec<(DateTime<Utc>, Decimal)> = history
+            .rows()
+            .filter_map(|row| row.equity.map(|equity| (row.timestamp, equity)))
+            .collect();
+        equity
+            .windows(2)
+            .filter(|w| !w[0].1.is_zero())
+            .map(|w| {
+                let flows = self.net_between(date(&w[0].0), date(&w[1].0));
+                (w[1].0, (w[1].1 - flows) / w[0].1 - Decimal::ONE)
+            })
+            .collect()
+    }
+
+    /// The time-weighted return over the history, compounding the flow-adjusted return of each
+    /// period.
+    pub fn time_weighted_return(&self, history: &PortfolioHistory) -> Decimal {
+        self.flow_adjusted_returns(history)
+            .into_iter()
+            .fold(Decimal::ONE, |acc, (_, r)| acc * (Decimal::ONE + r))
+            - Decimal::ONE
+    }
+}
+
+fn date(time: &DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&New_York).date_naive()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client_with_url;
+    use mockito::{mock, Matcher};
+
+    const ACTIVITIES: &'static str = r#"[
+        {"activity_type": "CSD", "id": "1", "date": "2021-03-01", "net_amount": "1000"},
+        {"activity_type": "DIV", "id": "2", "date": "2021-03-02", "net_amount": "5", "symbol": "T"},
+        {"activity_type": "CSD", "id": "3", "date": "2021-03-02", "net_amount": "100"},
+        {"activity_type": "JNLC", "id": "4", "date": "2021-03-04", "net_amount": "-331"}
+    ]"#;
+
+    // Daily bars labeled at midnight in New York, from 2021-03-01 to 2021-03-04.
+    const PORTFOLIO_HISTORY: &'static str = r#"{
+        "timestamp": [1614574800, 1614661200, 1614747600, 1614834000],
+        "equity": [1000, 1100, 1210, 1000],
+        "profit_loss": [0, 0, 110, 121],
+        "profit_loss_pct": [0, 0, 0.1, 0.11],
+        "base_value": 1000,
+        "timeframe": "1D"
+    }"#;
+
+    #[tokio::test]
+    async fn fetch_cash_flows() {
+        let _m = mock("GET", "/v2/account/activities")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("activity_types".into(), "CSD,CSW,JNLC,ACATC".into()),
+                Matcher::UrlEncoded("direction".into(), "asc".into()),
+            ]))
+            .with_body(ACTIVITIES)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let flows = CashFlows::fetch(&client, &CashFlows::request())
+            .await
+            .unwrap();
+        let amounts: Vec<_> = flows.flows().iter().map(|f| f.amount).collect();
+        assert_eq!(
+            amounts,
+            vec![
+                Decimal::new(1000, 0),
+                Decimal::new(100, 0),
+                Decimal::new(-331, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn flow_adjusted_returns() {
+        let activities: Vec<Activity> = serde_json::from_str(ACTIVITIES).unwrap();
+        let flows = CashFlows::new().record(&activities);
+        let history: PortfolioHistory = serde_json::from_str(PORTFOLIO_HISTORY).unwrap();
+
+        let returns: Vec<_> = flows
+            .flow_adjusted_returns(&history)
+            .into_iter()
+            .map(|(time, r)| (date(&time), r))
+            .collect();
+        assert_eq!(
+            returns,
+            vec![
+                (NaiveDate::from_ymd_opt(2021, 3, 2).unwrap(), Decimal::ZERO),
+                (
+                    NaiveDate::from_ymd_opt(2021, 3, 3).unwrap(),
+                    Decimal::new(1, 1)
+                ),
+                (
+                    NaiveDate::from_ymd_opt(2021, 3, 4).unwrap(),
+                    Decimal::new(1, 1)
+                ),
+            ]
+        );
+        assert_eq!(flows.time_weighted_return(&history), Decimal::new(21, 2));
+    }
+}
@@ -1,7 +1,8 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use vila::Request;
+use std::collections::VecDeque;
+use vila::{Client, Request};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 /// Clock object
@@ -43,12 +44,209 @@ impl Request for GetClock {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The offset of the server's clock from the local clock.
+pub struct ClockSkew {
+    /// The server's time less the local time
+    pub offset: Duration,
+    /// The most the offset can be wrong by, half the round trip time of the request it was
+    /// measured with
+    pub uncertainty: Duration,
+}
+
+impl ClockSkew {
+    /// Estimate the skew from a request sent and received at the given local times, which the
+    /// server answered with its timestamp. The server is assumed to have answered halfway through
+    /// the round trip.
+    pub fn from_round_trip(
+        sent: DateTime<Utc>,
+        server: DateTime<Utc>,
+        received: DateTime<Utc>,
+    ) -> Self {
+        let half_round_trip = (received - sent) / 2;
+        Self {
+            offset: server - (sent + half_round_trip),
+            uncertainty: half_round_trip,
+        }
+    }
+
+    /// Fetch the clock once and estimate the skew from the round trip.
+    pub async fn measure(client: &Client) -> Result<(Clock, Self), vila::Error> {
+        let sent = Utc::now();
+        let clock = client.send(&GetClock).await?;
+        let received = Utc::now();
+        let skew = Self::from_round_trip(sent, clock.timestamp, received);
+        Ok((clock, skew))
+    }
+
+    /// The current time on the server.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.to_server(Utc::now())
+    }
+
+    /// Convert a local time to the server's time.
+    pub fn to_server(&self, local: DateTime<Utc>) -> DateTime<Utc> {
+        local + self.offset
+    }
+
+    /// Convert a server time to the local time.
+    pub fn to_local(&self, server: DateTime<Utc>) -> DateTime<Utc> {
+        server - self.offset
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Estimates the skew between the local and server clocks from a number of round trips to the
+/// clock API. The estimate is taken from the sample with the shortest round trip, since it bounds
+/// the skew most tightly.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{clock::SkewEstimator, paper_client};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let mut estimator = SkewEstimator::new();
+///     estimator.sample_n(&client, 5).await?;
+///     let skew = estimator.estimate().unwrap();
+///     println!("{} ± {} ms", skew.offset, skew.uncertainty.num_milliseconds());
+///     println!("server time is {}", estimator.now());
+///     Ok(())
+/// }
+/// ```
+pub struct SkewEstimator {
+    samples: VecDeque<ClockSkew>,
+    max_samples: usize,
+}
+
+impl Default for SkewEstimator {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            max_samples: 16,
+        }
+    }
+}
+
+impl SkewEstimator {
+    /// Create an estimator that keeps the 16 most recent samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of most recent samples to keep. Older samples are dropped so that the estimate
+    /// follows drift of the local clock.
+    pub fn max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples.max(1);
+        self
+    }
+
+    /// Record a sample.
+    pub fn record(&mut self, skew: ClockSkew) {
+        if self.samples.len() == self.max_samples {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(skew);
+    }
+
+    /// Fetch the clock once and record the sample, returning the clock.
+    pub async fn sample(&mut self, client: &Client) -> Result<Clock, vila::Error> {
+        let (clock, skew) = ClockSkew::measure(client).await?;
+        self.record(skew);
+        Ok(clock)
+    }
+
+    /// Fetch the clock `n` times, recording each sample.
+    pub async fn sample_n(&mut self, client: &Client, n: usize) -> Result<(), vila::Error> {
+        for _ in 0..n {
+            self.sample(client).await?;
+        }
+        Ok(())
+    }
+
+    /// The recorded samples, oldest first.
+    pub fn samples(&self) -> impl Iterator<Item = &ClockSkew> {
+        self.samples.iter()
+    }
+
+    /// The sample with the lowest uncertainty, if any have been recorded.
+    pub fn estimate(&self) -> Option<ClockSkew> {
+        self.samples.iter().min_by_key(|s| s.uncertainty).copied()
+    }
+
+    /// The current time on the server, or the local time if no samples have been recorded.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.estimate().map_or_else(Utc::now, |skew| skew.now())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::client_with_url;
     use mockito::mock;
 
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let skew = ClockSkew::from_round_trip(
+            time("2021-11-26T12:00:00Z"),
+            time("2021-11-26T12:00:03Z"),
+            time("2021-11-26T12:00:01Z"),
+        );
+        assert_eq!(skew.offset, Duration::milliseconds(2500));
+        assert_eq!(skew.uncertainty, Duration::milliseconds(500));
+        assert_eq!(
+            skew.to_local(time("2021-11-26T12:00:03Z")),
+            time("2021-11-26T12:00:00.500Z")
+        );
+    }
+
+    #[test]
+    fn estimate() {
+        let mut estimator = SkewEstimator::new().max_samples(2);
+        assert_eq!(estimator.estimate(), None);
+        let sample = |offset, uncertainty| ClockSkew {
+            offset: Duration::milliseconds(offset),
+            uncertainty: Duration::milliseconds(uncertainty),
+        };
+        estimator.record(sample(100, 10));
+        estimator.record(sample(120, 30));
+        assert_eq!(estimator.estimate(), Some(sample(100, 10)));
+        estimator.record(sample(90, 20));
+        assert_eq!(estimator.samples().count(), 2);
+        assert_eq!(estimator.estimate(), Some(sample(90, 20)));
+    }
+
+    #[tokio::test]
+    async fn sample_clock() {
+        let _m = mock("GET", "/v2/clock")
+            .with_body(
+                r#"{
+                    "timestamp": "2021-11-26T12:00:00-05:00",
+                    "is_open": true,
+                    "next_open": "2021-11-29T09:30:00-05:00",
+                    "next_close": "2021-11-26T13:00:00-05:00"
+                }"#,
+            )
+            .expect(3)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let mut estimator = SkewEstimator::new();
+        estimator.sample_n(&client, 3).await.unwrap();
+        _m.assert();
+        let skew = estimator.estimate().unwrap();
+        let now = estimator.now();
+        assert!(now >= time("2021-11-26T17:00:00Z") - skew.uncertainty);
+        assert!(now <= time("2021-11-26T17:00:01Z") + skew.uncertainty);
+    }
+
     #[tokio::test]
     async fn get_clock() {
         let _m = mock("GET", "/v2/clock")
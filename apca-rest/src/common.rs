@@ -1,3 +1,4 @@
+use crate::utils::url_encode;
 use serde::{Deserialize, Serialize};
 use std::convert::From;
 use uuid::Uuid;
@@ -31,7 +32,8 @@ pub enum AssetClass {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
-#[serde(rename_all = "UPPERCASE")]
+#[serde(from = "String", into = "String")]
+#[non_exhaustive]
 /// Listing of exchanges
 pub enum Exchange {
     /// American Stock Exchange
@@ -50,17 +52,213 @@ pub enum Exchange {
     Otc,
     /// Crypto only
     ErisX,
+    /// FTX US, crypto only
+    Ftxu,
+    /// Coinbase, crypto only
+    Cbse,
+    /// Genesis, crypto only
+    Gnss,
+    /// Crypto only
+    Crypto,
+    /// An exchange not known to this crate.
+    Other(String),
+}
+
+impl From<String> for Exchange {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "AMEX" => Exchange::Amex,
+            "ARCA" => Exchange::Arca,
+            "BATS" => Exchange::Bats,
+            "NYSE" => Exchange::Nyse,
+            "NASDAQ" => Exchange::Nasdaq,
+            "NYSEARCA" => Exchange::NyseArca,
+            "OTC" => Exchange::Otc,
+            "ERISX" => Exchange::ErisX,
+            "FTXU" => Exchange::Ftxu,
+            "CBSE" => Exchange::Cbse,
+            "GNSS" => Exchange::Gnss,
+            "CRYPTO" => Exchange::Crypto,
+            _ => Exchange::Other(s),
+        }
+    }
+}
+
+impl From<Exchange> for String {
+    fn from(exchange: Exchange) -> Self {
+        match exchange {
+            Exchange::Amex => "AMEX".into(),
+            Exchange::Arca => "ARCA".into(),
+            Exchange::Bats => "BATS".into(),
+            Exchange::Nyse => "NYSE".into(),
+            Exchange::Nasdaq => "NASDAQ".into(),
+            Exchange::NyseArca => "NYSEARCA".into(),
+            Exchange::Otc => "OTC".into(),
+            Exchange::ErisX => "ERISX".into(),
+            Exchange::Ftxu => "FTXU".into(),
+            Exchange::Cbse => "CBSE".into(),
+            Exchange::Gnss => "GNSS".into(),
+            Exchange::Crypto => "CRYPTO".into(),
+            Exchange::Other(s) => s,
+        }
+    }
+}
+
+impl std::fmt::Display for Exchange {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&String::from(self.clone()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The reason a symbol or identifier could not be parsed.
+pub enum IdentifierError {
+    /// The symbol is empty.
+    EmptySymbol,
+    /// The symbol contains characters other than letters, digits, `.`, `-` and a single `/`
+    /// between the two halves of a crypto pair.
+    InvalidSymbol(String),
+    /// The exchange is empty or contains characters other than letters and digits.
+    InvalidExchange(String),
+    /// The asset class is not known.
+    InvalidAssetClass(String),
+    /// The identifier has more than a symbol, exchange and asset class.
+    TooManyParts(String),
+}
+
+impl std::fmt::Display for IdentifierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IdentifierError::EmptySymbol => write!(f, "symbol is empty"),
+            IdentifierError::InvalidSymbol(s) => write!(f, "invalid symbol {:?}", s),
+            IdentifierError::InvalidExchange(s) => write!(f, "invalid exchange {:?}", s),
+            IdentifierError::InvalidAssetClass(s) => write!(f, "unknown asset class {:?}", s),
+            IdentifierError::TooManyParts(s) => {
+                write!(f, "expected SYMBOL[:EXCHANGE[:ASSET_CLASS]], got {:?}", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IdentifierError {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+/// A validated ticker symbol, such as `AAPL`, `BRK.B` or the crypto pair `BTC/USD`. Symbols are
+/// stored in upper case.
+pub struct Symbol(String);
+
+impl Symbol {
+    /// Validate the symbol.
+    pub fn new<T: AsRef<str>>(symbol: T) -> Result<Self, IdentifierError> {
+        let symbol = symbol.as_ref().to_uppercase();
+        if symbol.is_empty() {
+            return Err(IdentifierError::EmptySymbol);
+        }
+        let valid_chars = symbol
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '/'));
+        let valid_pair = match symbol.split_once('/') {
+            Some((base, quote)) => !base.is_empty() && !quote.is_empty() && !quote.contains('/'),
+            None => true,
+        };
+        if !valid_chars || !valid_pair {
+            return Err(IdentifierError::InvalidSymbol(symbol));
+        }
+        Ok(Self(symbol))
+    }
+
+    /// Wrap a symbol reported by Alpaca without validating it.
+    pub(crate) fn unchecked<T: ToString>(symbol: T) -> Self {
+        Self(symbol.to_string())
+    }
+
+    /// The symbol as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the symbol is a crypto pair, such as `BTC/USD`.
+    pub fn is_crypto_pair(&self) -> bool {
+        self.0.contains('/')
+    }
+
+    /// The symbol encoded for use as a segment of a URL path.
+    pub fn url_encoded(&self) -> String {
+        url_encode(&self.0)
+    }
+}
+
+impl std::str::FromStr for Symbol {
+    type Err = IdentifierError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for Symbol {
+    type Error = IdentifierError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Symbol {
+    type Error = IdentifierError;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Symbol {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 /// Identifier that can be used to find an asset at Alpaca
 pub enum Identifier {
     /// Symbol, optionally with exchange and asset class
-    Symbol(String, Option<(String, Option<String>)>),
+    Symbol(Symbol, Option<(Exchange, Option<AssetClass>)>),
     /// Unique asset id
     AssetId(Uuid),
 }
 
+impl Identifier {
+    /// The identifier encoded for use as a segment of a URL path.
+    pub fn url_encoded(&self) -> String {
+        url_encode(&self.to_string())
+    }
+}
+
 impl std::fmt::Display for Identifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -70,6 +268,7 @@ impl std::fmt::Display for Identifier {
                 write!(f, "{}:{}", symbol, exchange)
             }
             Identifier::Symbol(symbol, Some((exchange, Some(asset_class)))) => {
+                let asset_class = serde_plain::to_string(asset_class).unwrap();
                 write!(f, "{}:{}:{}", symbol, exchange, asset_class)
             }
         }
@@ -82,67 +281,163 @@ impl From<Uuid> for Identifier {
     }
 }
 
-impl<'a> From<&'a str> for Identifier {
-    fn from(s: &'a str) -> Identifier {
+impl From<Symbol> for Identifier {
+    fn from(symbol: Symbol) -> Identifier {
+        Identifier::Symbol(symbol, None)
+    }
+}
+
+impl std::str::FromStr for Identifier {
+    type Err = IdentifierError;
+
+    /// Parse an asset ID, or a symbol optionally followed by `:EXCHANGE` and `:asset_class`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Ok(u) = Uuid::parse_str(s) {
-            Identifier::AssetId(u)
-        } else if let Some((symbol, rest)) = s.split_once(':') {
-            if let Some((exchange, asset_class)) = rest.split_once(':') {
-                Identifier::Symbol(
-                    symbol.to_string(),
-                    Some((exchange.to_string(), Some(asset_class.to_string()))),
-                )
-            } else {
-                Identifier::Symbol(symbol.to_string(), Some((rest.to_string(), None)))
-            }
-        } else {
-            Identifier::Symbol(s.to_string(), None)
+            return Ok(Identifier::AssetId(u));
         }
+        let mut parts = s.split(':');
+        let symbol = Symbol::new(parts.next().unwrap_or_default())?;
+        let exchange = parts
+            .next()
+            .map(|exchange| {
+                if exchange.is_empty() || !exchange.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(IdentifierError::InvalidExchange(exchange.to_string()));
+                }
+                Ok(Exchange::from(exchange.to_uppercase()))
+            })
+            .transpose()?;
+        let asset_class = parts
+            .next()
+            .map(|asset_class| {
+                serde_plain::from_str(asset_class)
+                    .map_err(|_| IdentifierError::InvalidAssetClass(asset_class.to_string()))
+            })
+            .transpose()?;
+        if parts.next().is_some() {
+            return Err(IdentifierError::TooManyParts(s.to_string()));
+        }
+        Ok(Identifier::Symbol(
+            symbol,
+            exchange.map(|exchange| (exchange, asset_class)),
+        ))
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Identifier {
+    type Error = IdentifierError;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        s.parse()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_fixtures::symbol;
 
     #[test]
     fn formatting() {
         assert_eq!(
-            Identifier::Symbol("AAPL".to_string(), None).to_string(),
+            Identifier::Symbol(symbol("AAPL"), None).to_string(),
             "AAPL".to_string()
         );
         assert_eq!(
-            Identifier::Symbol("AAPL".to_string(), Some(("NYSE".to_string(), None))).to_string(),
+            Identifier::Symbol(symbol("AAPL"), Some((Exchange::Nyse, None))).to_string(),
             "AAPL:NYSE".to_string()
         );
         assert_eq!(
             Identifier::Symbol(
-                "AAPL".to_string(),
-                Some(("NYSE".to_string(), Some("us_equity".to_string())))
+                symbol("AAPL"),
+                Some((Exchange::Nyse, Some(AssetClass::UsEquity)))
             )
             .to_string(),
             "AAPL:NYSE:us_equity".to_string()
         );
+        assert_eq!(
+            Identifier::Symbol(symbol("BTC/USD"), None).url_encoded(),
+            "BTC%2FUSD".to_string()
+        );
+        assert_eq!(
+            Identifier::Symbol(symbol("BRK.B"), Some((Exchange::Nyse, None))).url_encoded(),
+            "BRK.B%3ANYSE".to_string()
+        );
+    }
+
+    #[test]
+    fn exchanges() {
+        let exchanges: Vec<Exchange> =
+            serde_json::from_str(r#"["NYSEARCA", "ERISX", "FTXU", "CRYPTO", "IEX"]"#).unwrap();
+        assert_eq!(
+            exchanges,
+            vec![
+                Exchange::NyseArca,
+                Exchange::ErisX,
+                Exchange::Ftxu,
+                Exchange::Crypto,
+                Exchange::Other("IEX".to_string())
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&exchanges).unwrap(),
+            r#"["NYSEARCA","ERISX","FTXU","CRYPTO","IEX"]"#
+        );
+        assert_eq!(Exchange::Nasdaq.to_string(), "NASDAQ");
     }
 
     #[test]
     fn construction() {
-        let i: Identifier = "AAPL".into();
-        assert_eq!(i, Identifier::Symbol("AAPL".to_string(), None));
-        let i: Identifier = "AAPL:NYSE".into();
+        let i: Identifier = "AAPL".parse().unwrap();
+        assert_eq!(i, Identifier::Symbol(symbol("AAPL"), None));
+        let i: Identifier = "AAPL:NYSE".parse().unwrap();
+        assert_eq!(
+            i,
+            Identifier::Symbol(symbol("AAPL"), Some((Exchange::Nyse, None)))
+        );
+        let i: Identifier = "AAPL:NYSE:us_equity".parse().unwrap();
         assert_eq!(
             i,
-            Identifier::Symbol("AAPL".to_string(), Some(("NYSE".to_string(), None)))
+            Identifier::Symbol(
+                symbol("AAPL"),
+                Some((Exchange::Nyse, Some(AssetClass::UsEquity)))
+            )
         );
-        let i: Identifier = "AAPL:NYSE:us_equity".into();
+        let i: Identifier = "BTC/USD:FTXU:crypto".parse().unwrap();
         assert_eq!(
             i,
             Identifier::Symbol(
-                "AAPL".to_string(),
-                Some(("NYSE".to_string(), Some("us_equity".to_string())))
+                symbol("BTC/USD"),
+                Some((Exchange::Ftxu, Some(AssetClass::Crypto)))
             )
         );
-        let i: Identifier = "00000000-0000-0000-0000-000000000000".into();
+        let i: Identifier = "00000000-0000-0000-0000-000000000000".parse().unwrap();
         assert_eq!(i, Identifier::AssetId(Uuid::nil()))
     }
+
+    #[test]
+    fn invalid_identifiers() {
+        let parse = |s: &str| s.parse::<Identifier>().unwrap_err();
+        assert_eq!(
+            parse("AAPL:NYSE:bogus"),
+            IdentifierError::InvalidAssetClass("bogus".into())
+        );
+        assert_eq!(parse(""), IdentifierError::EmptySymbol);
+        assert_eq!(parse(":NYSE"), IdentifierError::EmptySymbol);
+        assert_eq!(parse("AAPL:"), IdentifierError::InvalidExchange("".into()));
+        assert_eq!(
+            parse("AAPL:NYSE:us_equity:x"),
+            IdentifierError::TooManyParts("AAPL:NYSE:us_equity:x".into())
+        );
+        assert_eq!(
+            parse("AA PL"),
+            IdentifierError::InvalidSymbol("AA PL".into())
+        );
+        assert_eq!(parse("BTC/"), IdentifierError::InvalidSymbol("BTC/".into()));
+        assert_eq!(
+            parse("A/B/C"),
+            IdentifierError::InvalidSymbol("A/B/C".into())
+        );
+        assert_eq!(symbol("brk.b"), "BRK.B");
+        assert!(serde_json::from_str::<Symbol>(r#""BTC//USD""#).is_err());
+    }
 }
@@ -0,0 +1,444 @@
+use crate::account::{Account, MAX_NON_PDT_DAYTRADES};
+use crate::account_activities::{Activity, Side as FillSide};
+use crate::calendar::Calendar;
+use crate::orders::{Side, SubmitOrder};
+use crate::positions::Position;
+use chrono::NaiveDate;
+use chrono_tz::America::New_York;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// The number of trading days in the rolling window used to count day trades.
+pub const DAYTRADE_WINDOW: usize = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+/// An order that was refused because it would flag the account as a pattern day trader, or
+/// because the account is flagged and does not have enough equity to day trade.
+pub struct PdtViolation {
+    /// Symbol of the refused order
+    pub symbol: String,
+    /// Number of day trades in the current window before the order
+    pub daytrade_count: u32,
+}
+
+impl fmt::Display for PdtViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "order for {} would be a day trade, with {} day trades already made",
+            self.symbol, self.daytrade_count
+        )
+    }
+}
+
+impl std::error::Error for PdtViolation {}
+
+#[derive(Clone, Debug, Default)]
+struct SymbolState {
+    qty: Decimal,
+    opened_long_today: Decimal,
+    opened_short_today: Decimal,
+}
+
+impl SymbolState {
+    /// Apply a trade of `qty` shares (negative for sells), returning whether it closes shares that
+    /// were opened on the same day. Closing trades are matched against shares opened today first.
+    fn apply(&mut self, qty: Decimal) -> bool {
+        let (position, opened_same_side, opened_other_side) = if qty.is_sign_positive() {
+            (
+                -self.qty,
+                &mut self.opened_long_today,
+                &mut self.opened_short_today,
+            )
+        } else {
+            (
+                self.qty,
+                &mut self.opened_short_today,
+                &mut self.opened_long_today,
+            )
+        };
+        let size = qty.abs();
+        let closed = size.min(position.max(Decimal::ZERO));
+        let day_trade = !closed.is_zero() && !opened_other_side.is_zero();
+        *opened_other_side -= closed.min(*opened_other_side);
+        *opened_same_side += size - closed;
+        self.qty += qty;
+        day_trade
+    }
+
+    fn new_day(&mut self) {
+        self.opened_long_today = Decimal::ZERO;
+        self.opened_short_today = Decimal::ZERO;
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Simulates FINRA's pattern day trader rule, so that orders which would get an account flagged can
+/// be refused before they are sent.
+///
+/// A day trade is counted when an order closes, in whole or in part, a position that was opened or
+/// increased on the same trading day. Closing trades are matched against shares opened that day
+/// before shares held overnight, and each order counts as at most one day trade regardless of how
+/// many executions it fills in. An account that makes more than 3 day trades in 5 trading days is
+/// flagged as a pattern day trader.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     account::{Account, GetAccount},
+///     account_activities::{Activity, ActivityType, GetAccountActivitiesByType},
+///     calendar::{Calendar, GetCalendar},
+///     day_trading::DayTradeSimulator,
+///     orders::{Side, SubmitOrder},
+///     paper_client,
+///     positions::{GetPositions, Position},
+/// };
+/// use chrono::{Duration, Utc};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let today = Utc::now().date_naive();
+///     let calendar: Vec<Calendar> = client
+///         .send(&GetCalendar::new().start(today - Duration::days(14)).end(today + Duration::days(14)))
+///         .await?;
+///     let fills: Vec<Activity> = client
+///         .send(&GetAccountActivitiesByType::new(ActivityType::Fill).after_date(today - Duration::days(14)))
+///         .await?;
+///     let positions: Vec<Position> = client.send(&GetPositions).await?;
+///     let account: Account = client.send(&GetAccount).await?;
+///
+///     let simulator = DayTradeSimulator::new(&calendar, today).replay(&fills, &positions);
+///     let order = SubmitOrder::new("AAPL".parse()?).qty(10).side(Side::Sell);
+///     simulator.check(&account, &order)?;
+///     client.send(&order).await?;
+///     Ok(())
+/// }
+/// ```
+pub struct DayTradeSimulator {
+    trading_days: Vec<NaiveDate>,
+    today: NaiveDate,
+    symbols: HashMap<String, SymbolState>,
+    daytrades: BTreeMap<NaiveDate, u32>,
+}
+
+impl DayTradeSimulator {
+    /// Create a new simulator for the given trading day. The calendar must cover at least the 5
+    /// trading days up to and including `today`, and should cover the following 4 trading days in
+    /// order to project the day trade count forward.
+    pub fn new(calendar: &[Calendar], today: NaiveDate) -> Self {
+        let mut trading_days: Vec<NaiveDate> = calendar.iter().map(|c| c.date).collect();
+        trading_days.sort_unstable();
+        trading_days.dedup();
+        Self {
+            trading_days,
+            today,
+            symbols: HashMap::new(),
+            daytrades: BTreeMap::new(),
+        }
+    }
+
+    /// Replay the fills made in the current window, along with the account's current positions,
+    /// to determine which day trades have been made and which positions were opened today.
+    /// Activities other than fills, and fills outside of the current window, are ignored.
+    pub fn replay(mut self, activities: &[Activity], positions: &[Position]) -> Self {
+        let window_start = self.window(self.today).first().copied();
+        let mut fills: Vec<_> = activities
+            .iter()
+            .filter_map(|a| match a {
+                Activity::TradeActivity {
+                    symbol,
+                    side,
+                    qty,
+                    transaction_time,
+                    order_id,
+                    ..
+                } => {
+                    let date = transaction_time.with_timezone(&New_York).date_naive();
+                    let qty = match side {
+                        FillSide::Buy => *qty,
+                        FillSide::Sell | FillSide::SellShort => -*qty,
+                    };
+                    Some((*transaction_time, date, symbol, qty, order_id))
+                }
+                Activity::NonTradeActivity(_) => None,
+            })
+            .filter(|(_, date, ..)| {
+                window_start.map(|start| *date >= start).unwrap_or(false) && *date <= self.today
+            })
+            .collect();
+        fills.sort_by_key(|(time, ..)| *time);
+
+        // Work back from the current positions to the positions held at the start of the window.
+        for position in positions {
+            self.symbols.entry(position.symbol.clone()).or_default().qty =
+                Decimal::from(position.qty);
+        }
+        for (_, _, symbol, qty, _) in &fills {
+            self.symbols.entry(symbol.to_string()).or_default().qty -= qty;
+        }
+
+        let mut current_day = None;
+        let mut counted = HashSet::new();
+        for (_, date, symbol, qty, order_id) in fills {
+            if current_day != Some(date) {
+                self.symbols.values_mut().for_each(SymbolState::new_day);
+                current_day = Some(date);
+            }
+            let state = self.symbols.entry(symbol.to_string()).or_default();
+            if state.apply(qty) && counted.insert(order_id) {
+                *self.daytrades.entry(date).or_default() += 1;
+            }
+        }
+        if current_day != Some(self.today) {
+            self.symbols.values_mut().for_each(SymbolState::new_day);
+        }
+        self
+    }
+
+    /// The number of day trades made in the 5 trading days up to and including today.
+    pub fn daytrade_count(&self) -> u32 {
+        self.count(self.today, &self.daytrades)
+    }
+
+    /// Whether each of the orders would be a day trade if sent, and filled, in order. Notional
+    /// orders on the closing side of a position are assumed to close the whole position.
+    pub fn classify(&self, orders: &[SubmitOrder]) -> Vec<bool> {
+        let mut symbols = self.symbols.clone();
+        orders
+            .iter()
+            .map(|order| {
+                let state = symbols.entry(order.symbol.to_string()).or_default();
+                let qty = match (order.qty, &order.side) {
+                    (Some(qty), _) => Decimal::from(qty),
+                    (None, Side::Buy) if state.qty.is_sign_negative() => state.qty.abs(),
+                    (None, Side::Sell) if state.qty.is_sign_positive() => state.qty,
+                    (None, _) => Decimal::ONE,
+                };
+                let qty = match order.side {
+                    Side::Buy => qty,
+                    Side::Sell => -qty,
+                };
+                state.apply(qty)
+            })
+            .collect()
+    }
+
+    /// Whether the order would be a day trade if sent and filled.
+    pub fn is_day_trade(&self, order: &SubmitOrder) -> bool {
+        self.classify(std::slice::from_ref(order))[0]
+    }
+
+    /// The day trade count for today and each of the following 4 trading days, assuming the given
+    /// orders are filled today and no other day trades are made. This shows when day trades fall
+    /// out of the rolling window.
+    pub fn projection(&self, orders: &[SubmitOrder]) -> Vec<(NaiveDate, u32)> {
+        let mut daytrades = self.daytrades.clone();
+        let new = self.classify(orders).into_iter().filter(|d| *d).count() as u32;
+        *daytrades.entry(self.today).or_default() += new;
+        let start = self.trading_days.partition_point(|d| *d < self.today);
+        std::iter::once(self.today)
+            .chain(
+                self.trading_days[start..]
+                    .iter()
+                    .copied()
+                    .filter(|d| *d > self.today),
+            )
+            .take(DAYTRADE_WINDOW)
+            .map(|date| (date, self.count(date, &daytrades)))
+            .collect()
+    }
+
+    /// Refuse the order if it is a day trade that would flag the account as a pattern day trader,
+    /// or if the account is already flagged and does not have the equity required to day trade.
+    /// The higher of the account's reported day trade count and the simulated count is used.
+    pub fn check(&self, account: &Account, order: &SubmitOrder) -> Result<(), PdtViolation> {
+        let daytrade_count = account.daytrade_count.max(self.daytrade_count());
+        let allowed = match account.daytrades_remaining() {
+            None => true,
+            Some(_) if account.pattern_day_trader => false,
+            Some(_) => daytrade_count < MAX_NON_PDT_DAYTRADES,
+        };
+        if allowed || !self.is_day_trade(order) {
+            Ok(())
+        } else {
+            Err(PdtViolation {
+                symbol: order.symbol.to_string(),
+                daytrade_count,
+            })
+        }
+    }
+
+    /// The trading days in the window ending on `date`.
+    fn window(&self, date: NaiveDate) -> &[NaiveDate] {
+        let end = self.trading_days.partition_point(|d| *d <= date);
+        &self.trading_days[end.saturating_sub(DAYTRADE_WINDOW)..end]
+    }
+
+    fn count(&self, date: NaiveDate, daytrades: &BTreeMap<NaiveDate, u32>) -> u32 {
+        let window = self.window(date);
+        match (window.first(), window.last()) {
+            (Some(start), Some(_)) => daytrades.range(*start..=date).map(|(_, n)| n).sum(),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{fill, position, with_fields, ACCOUNT};
+    use chrono::{Datelike, Weekday};
+    use serde_json::json;
+
+    fn calendar() -> Vec<Calendar> {
+        // Weekdays in March 2021.
+        (1..=31)
+            .filter_map(|d| NaiveDate::from_ymd_opt(2021, 3, d))
+            .filter(|d| d.weekday() != Weekday::Sat && d.weekday() != Weekday::Sun)
+            .map(|date| Calendar {
+                date,
+                open: chrono::NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                close: chrono::NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                session_open: None,
+                session_close: None,
+            })
+            .collect()
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2021, 3, d).unwrap()
+    }
+
+    fn at(d: u32, minute: u32) -> String {
+        format!("2021-03-{:02}T15:{:02}:00Z", d, minute)
+    }
+
+    #[test]
+    fn classify_orders() {
+        // AAPL: 100 held overnight, 50 bought today. TSLA: 10 held overnight. MSFT: shorted today.
+        let fills = vec![
+            fill("1", "AAPL", "buy", "100", "100", &at(9, 1)),
+            fill("2", "AAPL", "buy", "50", "100", &at(10, 2)),
+            fill("3", "TSLA", "buy", "10", "100", &at(9, 3)),
+            fill("4", "MSFT", "sell_short", "5", "100", &at(10, 4)),
+        ];
+        let positions = vec![
+            position("AAPL", 150, "100"),
+            position("TSLA", 10, "100"),
+            position("MSFT", -5, "100"),
+        ];
+        let simulator = DayTradeSimulator::new(&calendar(), day(10)).replay(&fills, &positions);
+        assert_eq!(simulator.daytrade_count(), 0);
+
+        let orders = vec![
+            SubmitOrder::new("TSLA".parse().unwrap())
+                .qty(10)
+                .side(Side::Sell),
+            SubmitOrder::new("TSLA".parse().unwrap())
+                .qty(5)
+                .side(Side::Buy),
+            SubmitOrder::new("AAPL".parse().unwrap())
+                .qty(20)
+                .side(Side::Sell),
+            SubmitOrder::new("AAPL".parse().unwrap())
+                .qty(30)
+                .side(Side::Sell),
+            SubmitOrder::new("AAPL".parse().unwrap())
+                .qty(100)
+                .side(Side::Sell),
+            SubmitOrder::new("MSFT".parse().unwrap())
+                .qty(5)
+                .side(Side::Buy),
+            SubmitOrder::new("GME".parse().unwrap())
+                .qty(5)
+                .side(Side::Buy),
+        ];
+        assert_eq!(
+            simulator.classify(&orders),
+            vec![false, false, true, true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn count_and_projection() {
+        // Day trades on the 4th, 8th and two on the 10th (the 10th being a Wednesday).
+        let fills = vec![
+            fill("1", "AAPL", "buy", "10", "100", &at(4, 1)),
+            fill("2", "AAPL", "sell", "10", "100", &at(4, 2)),
+            fill("3", "AAPL", "buy", "10", "100", &at(8, 3)),
+            fill("4", "AAPL", "sell", "5", "100", &at(8, 4)),
+            fill("4", "AAPL", "sell", "5", "100", &at(8, 4)),
+            fill("5", "TSLA", "buy", "10", "100", &at(10, 5)),
+            fill("6", "TSLA", "sell", "10", "100", &at(10, 6)),
+            fill("7", "MSFT", "sell_short", "10", "100", &at(10, 7)),
+            fill("8", "MSFT", "buy", "10", "100", &at(10, 8)),
+        ];
+        let simulator = DayTradeSimulator::new(&calendar(), day(10)).replay(&fills, &[]);
+        // The 4th is the first day of the window ending on the 10th.
+        assert_eq!(simulator.daytrade_count(), 4);
+        assert_eq!(
+            simulator.projection(&[]),
+            vec![
+                (day(10), 4),
+                (day(11), 3),
+                (day(12), 3),
+                (day(15), 2),
+                (day(16), 2),
+            ]
+        );
+
+        let simulator = DayTradeSimulator::new(&calendar(), day(11)).replay(&fills, &[]);
+        assert_eq!(simulator.daytrade_count(), 3);
+    }
+
+    #[test]
+    fn check_order() {
+        let fills = vec![
+            fill("1", "AAPL", "buy", "10", "100", &at(10, 1)),
+            fill("2", "AAPL", "sell", "10", "100", &at(10, 2)),
+            fill("3", "TSLA", "buy", "10", "100", &at(10, 3)),
+            fill("4", "TSLA", "sell", "5", "100", &at(10, 4)),
+            fill("5", "MSFT", "buy", "10", "100", &at(10, 5)),
+            fill("6", "MSFT", "sell", "10", "100", &at(10, 6)),
+            fill("7", "GME", "buy", "10", "100", &at(10, 7)),
+        ];
+        let positions = vec![position("TSLA", 5, "100"), position("GME", 10, "100")];
+        let simulator = DayTradeSimulator::new(&calendar(), day(10)).replay(&fills, &positions);
+        assert_eq!(simulator.daytrade_count(), 3);
+
+        let mut account: Account = with_fields(
+            ACCOUNT,
+            json!({
+                "buying_power": "20000",
+                "cash": "10000",
+                "daytrade_count": 3,
+                "daytrading_buying_power": "0",
+                "equity": "10000",
+                "last_equity": "10000",
+                "multiplier": "2",
+                "regt_buying_power": "20000",
+            }),
+        );
+
+        let closing = SubmitOrder::new("GME".parse().unwrap())
+            .qty(10)
+            .side(Side::Sell);
+        let opening = SubmitOrder::new("GME".parse().unwrap())
+            .qty(10)
+            .side(Side::Buy);
+        assert_eq!(
+            simulator.check(&account, &closing),
+            Err(PdtViolation {
+                symbol: "GME".into(),
+                daytrade_count: 3
+            })
+        );
+        assert_eq!(simulator.check(&account, &opening), Ok(()));
+
+        account.pattern_day_trader = true;
+        account.equity = Decimal::new(30000, 0);
+        assert_eq!(simulator.check(&account, &closing), Ok(()));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::account_activities::{Activity, ActivityType, GetAccountActivities};
+use crate::positions::Position;
+use crate::Sort;
+use chrono::{Datelike, NaiveDate};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use vila::Client;
+
+/// The activity types recorded by the income ledger.
+pub const INCOME_ACTIVITY_TYPES: &[ActivityType] = &[
+    ActivityType::Dividend,
+    ActivityType::DividendLongTermCapitalGain,
+    ActivityType::DividendShortTermCapitalGain,
+    ActivityType::DividendTaxExempt,
+    ActivityType::DividendReturnOfCapital,
+    ActivityType::DividendForeignTaxWithheld,
+    ActivityType::DividendNraWithheld,
+    ActivityType::DividendTefraWithheld,
+    ActivityType::DividendFee,
+    ActivityType::Interest,
+    ActivityType::InterestNraWithheld,
+    ActivityType::InterestTefraWithheld,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// How an income activity is accounted for.
+pub enum IncomeCategory {
+    /// Dividends, capital gain distributions and interest
+    Income,
+    /// Tax withheld from dividends or interest
+    Withholding,
+    /// Fees charged on dividends
+    Fee,
+    /// Distributions that return capital, which reduce cost basis rather than being income
+    ReturnOfCapital,
+}
+
+impl IncomeCategory {
+    /// The category of the activity type, if it is recorded by the income ledger.
+    pub fn of(activity_type: &ActivityType) -> Option<Self> {
+        use ActivityType::*;
+        match activity_type {
+            Dividend
+            | DividendLongTermCapitalGain
+            | DividendShortTermCapitalGain
+            | DividendTaxExempt
+            | Interest => Some(IncomeCategory::Income),
+            DividendForeignTaxWithheld
+            | DividendNraWithheld
+            | DividendTefraWithheld
+            | InterestNraWithheld
+            | InterestTefraWithheld => Some(IncomeCategory::Withholding),
+            DividendFee => Some(IncomeCategory::Fee),
+            DividendReturnOfCapital => Some(IncomeCategory::ReturnOfCapital),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A single income activity.
+pub struct IncomeEntry {
+    /// The date of the activity
+    pub date: NaiveDate,
+    /// The symbol of the security paying the income. Not present for interest.
+    pub symbol: Option<String>,
+    /// The type of the activity
+    pub activity_type: ActivityType,
+    /// How the activity is accounted for
+    pub category: IncomeCategory,
+    /// The net amount of the activity, negative for withholding and fees
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// Income totals over a set of activities.
+pub struct IncomeSummary {
+    /// Dividends, capital gain distributions and interest, before withholding
+    pub gross: Decimal,
+    /// Tax withheld
+    pub withheld: Decimal,
+    /// Fees charged
+    pub fees: Decimal,
+    /// Return of capital, which is not included in the income
+    pub return_of_capital: Decimal,
+}
+
+impl IncomeSummary {
+    /// Income net of withholding and fees.
+    pub fn net(&self) -> Decimal {
+        self.gross - self.withheld - self.fees
+    }
+}
+
+impl AddAssign<&IncomeEntry> for IncomeSummary {
+    fn add_assign(&mut self, entry: &IncomeEntry) {
+        match entry.category {
+            IncomeCategory::Income => self.gross += entry.amount,
+            IncomeCategory::Withholding => self.withheld -= entry.amount,
+            IncomeCategory::Fee => self.fees -= entry.amount,
+            IncomeCategory::ReturnOfCapital => self.return_of_capital += entry.amount,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// A ledger of dividend and interest income, summarized by symbol, month and year.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{income::IncomeLedger, paper_client, positions::{GetPositions, Position}};
+/// use chrono::NaiveDate;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let request = IncomeLedger::request().after_date(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
+///     let ledger = IncomeLedger::fetch(&client, &request).await?;
+///     for (year, summary) in ledger.by_year() {
+///         println!("{}: {}", year, summary.net());
+///     }
+///     let positions: Vec<Position> = client.send(&GetPositions).await?;
+///     let yields = ledger.yield_on_cost(
+///         &positions,
+///         NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+///         NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+///     );
+///     Ok(())
+/// }
+/// ```
+pub struct IncomeLedger {
+    entries: Vec<IncomeEntry>,
+}
+
+impl IncomeLedger {
+    /// Create an empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A request for all the income activity types, oldest first. Date filters can be added to the
+    /// request before it is passed to `fetch`.
+    pub fn request() -> GetAccountActivities {
+        GetAccountActivities::new()
+            .add_activities(INCOME_ACTIVITY_TYPES)
+            .sort(Sort::Ascending)
+    }
+
+    /// Fetch every page of the request and record the income activities.
+    pub async fn fetch(
+        client: &Client,
+        request: &GetAccountActivities,
+    ) -> Result<Self, vila::Error> {
+        let pages: Vec<Vec<Activity>> = client.send_paginated(request).try_collect().await?;
+        Ok(Self::new().record(pages.iter().flatten()))
+    }
+
+    /// Record the income activities. Other activities are ignored.
+    pub fn record<'a, T: IntoIterator<Item = &'a Activity>>(mut self, activities: T) -> Self {
+        for activity in activities {
+            if let Activity::NonTradeActivity(activity) = activity {
+                if let Some(category) = IncomeCategory::of(&activity.activity_type) {
+                    let symbol = match activity.activity_type {
+                        ActivityType::Interest
+                        | ActivityType::InterestNraWithheld
+                        | ActivityType::InterestTefraWithheld => None,
+                        _ => activity.symbol().map(ToString::to_string),
+                    };
+                    self.entries.push(IncomeEntry {
+                        date: activity.date,
+                        symbol,
+                        activity_type: activity.activity_type.clone(),
+                        category,
+                        amount: activity.net_amount,
+                    });
+                }
+            }
+        }
+        self.entries.sort_by_key(|e| e.date);
+        self
+    }
+
+    /// The recorded entries, oldest first.
+    pub fn entries(&self) -> &[IncomeEntry] {
+        &self.entries
+    }
+
+    /// Totals over all the entries.
+    pub fn total(&self) -> IncomeSummary {
+        let mut summary = IncomeSummary::default();
+        self.entries.iter().for_each(|e| summary += e);
+        summary
+    }
+
+    /// Totals per symbol. Interest, which is not paid by a symbol, is under `None`.
+    pub fn by_symbol(&self) -> BTreeMap<Option<String>, IncomeSummary> {
+        self.summarize(|e| e.symbol.clone())
+    }
+
+    /// Totals per year and month.
+    pub fn by_month(&self) -> BTreeMap<(i32, u32), IncomeSummary> {
+        self.summarize(|e| (e.date.year(), e.date.month()))
+    }
+
+    /// Totals per year.
+    pub fn by_year(&self) -> BTreeMap<i32, IncomeSummary> {
+        self.summarize(|e| e.date.year())
+    }
+
+    /// Net income received from each held symbol between `start` and `end` inclusive, as a fraction
+    /// of the position's cost basis. Passing a one year range gives the annual yield on cost.
+    /// Positions without a positive cost basis are skipped.
+    pub fn yield_on_cost(
+        &self,
+        positions: &[Position],
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> BTreeMap<String, Decimal> {
+        let mut income: BTreeMap<&str, IncomeSummary> = BTreeMap::new();
+        for entry in &self.entries {
+            if let (Some(symbol), true) = (&entry.symbol, entry.date >= start && entry.date <= end)
+            {
+                *income.entry(symbol).or_default() += entry;
+            }
+        }
+        positions
+            .iter()
+            .filter(|p| p.cost_basis.is_sign_positive() && !p.cost_basis.is_zero())
+            .map(|p| {
+                let net = income
+                    .get(p.symbol.as_str())
+                    .map(IncomeSummary::net)
+                    .unwrap_or_default();
+                (p.symbol.clone(), net / p.cost_basis)
+            })
+            .collect()
+    }
+
+    fn summarize<K: Ord, F: Fn(&IncomeEntry) -> K>(&self, key: F) -> BTreeMap<K, IncomeSummary> {
+        let mut summaries: BTreeMap<K, IncomeSummary> = BTreeMap::new();
+        for entry in &self.entries {
+            *summaries.entry(key(entry)).or_default() += entry;
+        }
+        summaries
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client_with_url;
+    use crate::test_fixtures::position;
+    use mockito::{mock, Matcher};
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn ym(year: i32, month: u32) -> (i32, u32) {
+        (year, month)
+    }
+
+    const ACTIVITIES: &'static str = r#"[
+        {"activity_type": "DIV", "id": "1", "date": "2021-03-15", "net_amount": "10", "symbol": "T", "qty": "20", "per_share_amount": "0.5"},
+        {"activity_type": "DIVNRA", "id": "2", "date": "2021-03-15", "net_amount": "-3", "symbol": "T"},
+        {"activity_type": "DIVROC", "id": "3", "date": "2021-03-20", "net_amount": "2", "symbol": "T", "qty": "20", "per_share_amount": "0.1"},
+        {"activity_type": "INT", "id": "4", "date": "2021-03-31", "net_amount": "0.5"},
+        {"activity_type": "DIV", "id": "5", "date": "2021-06-15", "net_amount": "10", "symbol": "T", "qty": "20", "per_share_amount": "0.5"},
+        {"activity_type": "DIVFEE", "id": "6", "date": "2021-06-15", "net_amount": "-0.25", "symbol": "T"},
+        {"activity_type": "DIV", "id": "7", "date": "2022-01-10", "net_amount": "4", "symbol": "MSFT", "qty": "10", "per_share_amount": "0.4"},
+        {"activity_type": "CSD", "id": "8", "date": "2021-01-04", "net_amount": "1000"}
+    ]"#;
+
+    #[tokio::test]
+    async fn fetch_ledger() {
+        let _m = mock("GET", "/v2/account/activities")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded(
+                    "activity_types".into(),
+                    "DIV,DIVCGL,DIVCGS,DIVTXEX,DIVROC,DIVFT,DIVNRA,DIVTW,DIVFEE,INT,INTNRA,INTTW"
+                        .into(),
+                ),
+                Matcher::UrlEncoded("direction".into(), "asc".into()),
+            ]))
+            .with_body(ACTIVITIES)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let ledger = IncomeLedger::fetch(&client, &IncomeLedger::request())
+            .await
+            .unwrap();
+        assert_eq!(ledger.entries().len(), 7);
+        assert_eq!(
+            ledger.total(),
+            IncomeSummary {
+                gross: d("24.5"),
+                withheld: d("3"),
+                fees: d("0.25"),
+                return_of_capital: d("2"),
+            }
+        );
+        assert_eq!(ledger.total().net(), d("21.25"));
+    }
+
+    #[test]
+    fn summaries() {
+        let activities: Vec<Activity> = serde_json::from_str(ACTIVITIES).unwrap();
+        let ledger = IncomeLedger::new().record(&activities);
+
+        let by_symbol: Vec<_> = ledger
+            .by_symbol()
+            .into_iter()
+            .map(|(symbol, summary)| (symbol, summary.net()))
+            .collect();
+        assert_eq!(
+            by_symbol,
+            vec![
+                (None, d("0.5")),
+                (Some("MSFT".into()), d("4")),
+                (Some("T".into()), d("16.75")),
+            ]
+        );
+
+        let by_month: Vec<_> = ledger
+            .by_month()
+            .into_iter()
+            .map(|(month, summary)| (month, summary.net()))
+            .collect();
+        assert_eq!(
+            by_month,
+            vec![
+                (ym(2021, 3), d("7.5")),
+                (ym(2021, 6), d("9.75")),
+                (ym(2022, 1), d("4")),
+            ]
+        );
+
+        let by_year: Vec<_> = ledger
+            .by_year()
+            .into_iter()
+            .map(|(year, summary)| (year, summary.net()))
+            .collect();
+        assert_eq!(by_year, vec![(2021, d("17.25")), (2022, d("4"))]);
+    }
+
+    #[test]
+    fn yield_on_cost() {
+        let activities: Vec<Activity> = serde_json::from_str(ACTIVITIES).unwrap();
+        let ledger = IncomeLedger::new().record(&activities);
+        let positions = vec![
+            position("T", 20, "25"),
+            position("MSFT", 20, "100"),
+            position("GME", 20, "0"),
+        ];
+        let yields = ledger.yield_on_cost(
+            &positions,
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+        );
+        assert_eq!(
+            yields.into_iter().collect::<Vec<_>>(),
+            vec![("MSFT".into(), Decimal::ZERO), ("T".into(), d("0.0335"))]
+        );
+    }
+}
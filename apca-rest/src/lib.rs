@@ -30,6 +30,9 @@ pub mod account_activities;
 /// settings. These configurations control various allow you to modify settings to suit your
 /// trading needs.
 pub mod account_configurations;
+/// The account monitor polls the account API and reports changes to the account's status, flags,
+/// day trade count and equity, and can refuse orders while the account is not in a state to trade.
+pub mod account_monitor;
 /// The assets API serves as the master list of assets available for trade and data consumption
 /// from Alpaca. Assets are sorted by asset class, exchange and symbol. Some assets are only
 /// available for data consumption via Polygon, and are not tradable with Alpaca. These assets will
@@ -41,10 +44,20 @@ pub mod assets;
 /// response also contains the specific open and close times for the market days, taking into
 /// account early closures.
 pub mod calendar;
+/// Cash flows extracts deposits, withdrawals and other external cash flows from the account
+/// activities, and uses them to compute flow-adjusted returns from the portfolio history.
+pub mod cash_flows;
 /// The clock API serves the current market timestamp, whether or not the market is currently open,
-/// as well as the times of the next market open and close.
+/// as well as the times of the next market open and close. The server's timestamp is used to
+/// estimate the skew of the local clock.
 pub mod clock;
 mod common;
+/// The day trading simulator replays recent fills to count day trades under FINRA's pattern day
+/// trader rule, and classifies orders before they are sent so that the account is not flagged.
+pub mod day_trading;
+/// The income ledger records dividend and interest activities, and summarizes the income net of
+/// withholding by symbol, month and year.
+pub mod income;
 /// The Orders API allows a user to monitor, place and cancel their orders with Alpaca. Each order
 /// has a unique identifier provided by the client. This client-side unique order ID will be
 /// automatically generated by the system if not provided by the client, and will be returned as
@@ -53,6 +66,9 @@ mod common;
 /// orders at Alpaca will also be sent over the streaming interface, which is the recommended
 /// method of maintaining order state.
 pub mod orders;
+/// Performance computes returns, volatility, risk-adjusted ratios and drawdowns from the portfolio
+/// history.
+pub mod performance;
 /// The portfolio history API returns the timeseries data for equity and profit loss information of
 /// the account.
 pub mod portfolio_history;
@@ -61,7 +77,28 @@ pub mod portfolio_history;
 /// updated live as price information is updated. Once a position is closed, it will no longer be
 /// queryable through this API.
 pub mod positions;
+/// The rebalance module computes the orders needed to move the account's current positions to a
+/// set of target weights or dollar amounts, so they can be reviewed before being sent.
+pub mod rebalance;
+/// The reconciliation module replays the fills and corporate actions reported by the account
+/// activities API to rebuild the positions they imply, and compares them against the positions
+/// reported by Alpaca.
+pub mod reconciliation;
+/// The session scheduler produces timers and streams for market session events, such as the open
+/// or a number of minutes before the close, from the trading calendar and the market clock.
+pub mod scheduler;
+/// Tax lots are built from fills to compute realized gains and losses, using FIFO, LIFO, HIFO or
+/// specifically identified lots, and adjusting for stock splits and wash sales.
+pub mod tax_lots;
+#[cfg(test)]
+mod test_fixtures;
+/// Tick sizes round order prices and quantities to the increments an asset can be traded in, using
+/// the asset's metadata and the sub-penny rule.
+pub mod tick_size;
 mod utils;
+/// Watchlist sync computes the changes that bring the account's watchlists to a declared set of
+/// names and ordered symbols, so they can be reviewed as a dry run before being applied.
+pub mod watchlist_sync;
 /// The watchlist API provides CRUD operation for the account’s watchlist. An account can have
 /// multiple watchlists and each is uniquely identified by `id` but can also be addressed by
 /// user-defined `name`. Each watchlist is an ordered list of assets.
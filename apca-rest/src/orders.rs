@@ -1,4 +1,4 @@
-use crate::{AssetClass, Sort};
+use crate::{AssetClass, Sort, Symbol};
 use chrono::prelude::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -557,11 +557,11 @@ impl Request for GetOrder {
 /// use rust_decimal::Decimal;
 ///
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let order: Order = client
 ///         .send(
-///             &SubmitOrder::new("AAPL")
+///             &SubmitOrder::new("AAPL".parse()?)
 ///                 .qty(2)
 ///                 .side(Side::Sell)
 ///                 .time_in_force(TimeInForce::Day)
@@ -575,15 +575,17 @@ impl Request for GetOrder {
 /// }
 /// ```
 pub struct SubmitOrder {
-    symbol: String,
+    pub(crate) symbol: Symbol,
     #[serde(
-        deserialize_with = "crate::utils::from_str",
-        serialize_with = "crate::utils::to_string"
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::utils::to_string_optional"
     )]
-    qty: usize,
-    side: Side,
+    pub(crate) qty: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) notional: Option<Decimal>,
+    pub(crate) side: Side,
     #[serde(flatten, rename(serialize = "type"))]
-    order_type: OrderType,
+    pub(crate) order_type: OrderType,
     time_in_force: TimeInForce,
     extended_hours: bool,
     client_order_id: Option<String>,
@@ -593,10 +595,11 @@ pub struct SubmitOrder {
 
 impl SubmitOrder {
     /// Create a new request.
-    pub fn new<T: ToString>(symbol: T) -> Self {
+    pub fn new(symbol: Symbol) -> Self {
         Self {
-            symbol: symbol.to_string(),
-            qty: 1,
+            symbol,
+            qty: Some(1),
+            notional: None,
             side: Side::Buy,
             order_type: OrderType::market(),
             time_in_force: TimeInForce::GoodTilCancelled,
@@ -606,9 +609,18 @@ impl SubmitOrder {
         }
     }
 
-    /// Update the quantity of the order.
+    /// Update the quantity of the order. Replaces any notional value previously set.
     pub fn qty(mut self, qty: usize) -> Self {
-        self.qty = qty;
+        self.qty = Some(qty);
+        self.notional = None;
+        self
+    }
+
+    /// Set the dollar amount to trade instead of a quantity. Only valid for market orders on
+    /// fractionable assets with a `Day` time-in-force. Replaces any quantity previously set.
+    pub fn notional(mut self, notional: Decimal) -> Self {
+        self.notional = Some(notional);
+        self.qty = None;
         self
     }
 
@@ -850,6 +862,7 @@ impl Request for CancelAllOrders {
 mod tests {
     use super::*;
     use crate::client_with_url;
+    use crate::test_fixtures::ORDER;
     use mockito::{mock, Matcher};
 
     #[test]
@@ -921,7 +934,7 @@ mod tests {
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        let req = SubmitOrder::new("AAPL")
+        let req = SubmitOrder::new("AAPL".parse().unwrap())
             .qty(15)
             .time_in_force(TimeInForce::Day)
             .client_order_id("904837e3-3b76-47ec-b432-046db621571b");
@@ -940,7 +953,7 @@ mod tests {
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        let req = SubmitOrder::new("SPY")
+        let req = SubmitOrder::new("SPY".parse().unwrap())
             .client_order_id("904837e3-3b76-47ec-b432-046db621571b")
             .qty(100)
             .time_in_force(TimeInForce::GoodTilCancelled)
@@ -997,38 +1010,6 @@ mod tests {
             .unwrap();
     }
 
-    const ORDER: &'static str = r#"{
-        "id": "904837e3-3b76-47ec-b432-046db621571b",
-	    "client_order_id": "904837e3-3b76-47ec-b432-046db621571b",
-	    "created_at": "2018-10-05T05:48:59Z",
-	    "updated_at": "2018-10-05T05:48:59Z",
-	    "submitted_at": "2018-10-05T05:48:59Z",
-	    "filled_at": "2018-10-05T05:48:59Z",
-	    "expired_at": "2018-10-05T05:48:59Z",
-	    "canceled_at": "2018-10-05T05:48:59Z",
-	    "failed_at": "2018-10-05T05:48:59Z",
-	    "replaced_at": "2018-10-05T05:48:59Z",
-	    "replaced_by": "904837e3-3b76-47ec-b432-046db621571b",
-	    "replaces": null,
-	    "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
-	    "symbol": "AAPL",
-	    "asset_class": "us_equity",
-	    "qty": "15",
-	    "filled_qty": "0",
-	    "type": "market",
-	    "side": "buy",
-	    "time_in_force": "day",
-	    "limit_price": "107.00",
-	    "stop_price": "106.00",
-	    "filled_avg_price": "106.00",
-	    "status": "accepted",
-	    "extended_hours": false,
-	    "legs": null,
-        "trail_price": "1.05",
-        "trail_percent": null,
-        "hwm": "108.05"
-    }"#;
-
     const COMPLEX_ORDER: &'static str = r#"{
         "id": "904837e3-3b76-47ec-b432-046db621571b",
 	    "client_order_id": "904837e3-3b76-47ec-b432-046db621571b",
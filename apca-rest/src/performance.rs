@@ -0,0 +1,404 @@
+use crate::portfolio_history::PortfolioHistory;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+#[derive(Clone, Debug, PartialEq)]
+/// A decline from a peak in value.
+pub struct Drawdown {
+    /// The time of the peak
+    pub peak: DateTime<Utc>,
+    /// The time of the lowest value after the peak
+    pub trough: DateTime<Utc>,
+    /// The time the value first got back to the peak, if it has
+    pub recovery: Option<DateTime<Utc>>,
+    /// The decline from the peak to the trough, as a positive fraction of the peak
+    pub depth: f64,
+}
+
+impl Drawdown {
+    /// The time from the peak to the recovery, if the value has recovered.
+    pub fn duration(&self) -> Option<Duration> {
+        self.recovery.map(|recovery| recovery - self.peak)
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Return and risk statistics of a series of periodic returns.
+///
+/// Statistics are annualized using the number of periods per year, which defaults to that of the
+/// history's timeframe. Ratios are computed with `f64`, as they involve roots and powers.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     paper_client,
+///     performance::Performance,
+///     portfolio_history::{GetPortfolioHistory, Period, PortfolioHistory, Timeframe},
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let history: PortfolioHistory = client
+///         .send(
+///             &GetPortfolioHistory::new()
+///                 .period(Period::years(1))
+///                 .timeframe(Timeframe::OneDay),
+///         )
+///         .await?;
+///     let performance = Performance::new(&history);
+///     println!("Sharpe ratio: {:?}", performance.sharpe_ratio(0.02));
+///     println!("Max drawdown: {:?}", performance.max_drawdown());
+///     Ok(())
+/// }
+/// ```
+pub struct Performance {
+    /// Value of the portfolio relative to the start, beginning with 1 at the start.
+    curve: Vec<(DateTime<Utc>, f64)>,
+    periods_per_year: f64,
+}
+
+impl Performance {
+    /// Compute the returns of the history's equity. Elements without equity, and those before the
+    /// account had equity, are skipped. The returns include the effect of deposits and
+    /// withdrawals; use `from_returns` with `CashFlows::flow_adjusted_returns` to exclude them.
+    pub fn new(history: &PortfolioHistory) -> Self {
+        let rows: Vec<_> = history
+            .rows()
+            .filter_map(|row| row.equity.map(|equity| (row.timestamp, equity)))
+            .skip_while(|(_, equity)| *equity <= Decimal::ZERO)
+            .collect();
+        let base = rows
+            .first()
+            .map(|(_, equity)| *equity)
+            .unwrap_or(Decimal::ONE);
+        Self {
+            curve: rows
+                .iter()
+                .map(|(timestamp, equity)| (*timestamp, to_f64(equity / base)))
+                .collect(),
+            periods_per_year: history.timeframe.periods_per_year(),
+        }
+    }
+
+    /// Build the statistics from returns for the periods ending at each time, starting from
+    /// `start`.
+    pub fn from_returns<T: IntoIterator<Item = (DateTime<Utc>, Decimal)>>(
+        start: DateTime<Utc>,
+        returns: T,
+        periods_per_year: f64,
+    ) -> Self {
+        let mut value = 1.0;
+        let curve = std::iter::once((start, value))
+            .chain(returns.into_iter().map(|(time, r)| {
+                value *= 1.0 + to_f64(r);
+                (time, value)
+            }))
+            .collect();
+        Self {
+            curve,
+            periods_per_year,
+        }
+    }
+
+    /// Override the number of periods per year used to annualize statistics.
+    pub fn periods_per_year(mut self, periods_per_year: f64) -> Self {
+        self.periods_per_year = periods_per_year;
+        self
+    }
+
+    /// The return of each period, labeled with the time at the end of the period. Periods that
+    /// start without a positive value, such as after the account was emptied, have no defined
+    /// return and are skipped.
+    pub fn returns(&self) -> Vec<(DateTime<Utc>, f64)> {
+        self.curve
+            .windows(2)
+            .filter(|w| w[0].1 > 0.0)
+            .map(|w| (w[1].0, w[1].1 / w[0].1 - 1.0))
+            .collect()
+    }
+
+    /// The return over the whole series.
+    pub fn cumulative_return(&self) -> f64 {
+        self.curve.last().map(|(_, v)| v - 1.0).unwrap_or_default()
+    }
+
+    /// The compound annual growth rate over the series.
+    pub fn annualized_return(&self) -> f64 {
+        let periods = self.curve.len().saturating_sub(1);
+        if periods == 0 {
+            return 0.0;
+        }
+        (1.0 + self.cumulative_return()).powf(self.periods_per_year / periods as f64) - 1.0
+    }
+
+    /// The annualized sample standard deviation of the returns.
+    pub fn volatility(&self) -> f64 {
+        let returns: Vec<f64> = self.returns().into_iter().map(|(_, r)| r).collect();
+        if returns.len() < 2 {
+            return 0.0;
+        }
+        let mean = mean(&returns);
+        let variance =
+            returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        (variance * self.periods_per_year).sqrt()
+    }
+
+    /// The annualized mean excess return over the volatility, given an annual risk free rate.
+    /// `None` if the volatility is zero.
+    pub fn sharpe_ratio(&self, risk_free_rate: f64) -> Option<f64> {
+        let volatility = self.volatility();
+        if volatility == 0.0 {
+            return None;
+        }
+        Some(self.mean_excess_return(risk_free_rate) * self.periods_per_year / volatility)
+    }
+
+    /// The annualized mean excess return over the downside deviation, given an annual risk free
+    /// rate. `None` if no return is below the risk free rate.
+    pub fn sortino_ratio(&self, risk_free_rate: f64) -> Option<f64> {
+        let rate = risk_free_rate / self.periods_per_year;
+        let returns = self.returns();
+        if returns.is_empty() {
+            return None;
+        }
+        let downside = (returns
+            .iter()
+            .map(|(_, r)| (r - rate).min(0.0).powi(2))
+            .sum::<f64>()
+            / returns.len() as f64
+            * self.periods_per_year)
+            .sqrt();
+        if downside == 0.0 {
+            return None;
+        }
+        Some(self.mean_excess_return(risk_free_rate) * self.periods_per_year / downside)
+    }
+
+    /// Every drawdown in the series, in order.
+    pub fn drawdowns(&self) -> Vec<Drawdown> {
+        let mut drawdowns = Vec::new();
+        let mut peak = match self.curve.first() {
+            Some(first) => *first,
+            None => return drawdowns,
+        };
+        let mut current: Option<Drawdown> = None;
+        for &(time, value) in &self.curve[1..] {
+            if value >= peak.1 {
+                if let Some(mut drawdown) = current.take() {
+                    drawdown.recovery = Some(time);
+                    drawdowns.push(drawdown);
+                }
+                peak = (time, value);
+                continue;
+            }
+            let depth = 1.0 - value / peak.1;
+            let drawdown = current.get_or_insert(Drawdown {
+                peak: peak.0,
+                trough: time,
+                recovery: None,
+                depth,
+            });
+            if depth > drawdown.depth {
+                drawdown.trough = time;
+                drawdown.depth = depth;
+            }
+        }
+        drawdowns.extend(current);
+        drawdowns
+    }
+
+    /// The deepest drawdown in the series.
+    pub fn max_drawdown(&self) -> Option<Drawdown> {
+        self.drawdowns()
+            .into_iter()
+            .fold(None, |max: Option<Drawdown>, d| match max {
+                Some(max) if max.depth >= d.depth => Some(max),
+                _ => Some(d),
+            })
+    }
+
+    /// The longest time spent below a previous peak, counting a drawdown that has not recovered
+    /// up to the end of the series.
+    pub fn max_drawdown_duration(&self) -> Duration {
+        let end = match self.curve.last() {
+            Some((end, _)) => *end,
+            None => return Duration::zero(),
+        };
+        self.drawdowns()
+            .iter()
+            .map(|d| d.recovery.unwrap_or(end) - d.peak)
+            .max()
+            .unwrap_or_else(Duration::zero)
+    }
+
+    /// Apply `f` to each window of `window` periods, labeled with the time at the end of the
+    /// window. Windows that start without a positive value are skipped.
+    pub fn rolling<F: Fn(&Performance) -> f64>(
+        &self,
+        window: usize,
+        f: F,
+    ) -> Vec<(DateTime<Utc>, f64)> {
+        if window == 0 {
+            return Vec::new();
+        }
+        self.curve
+            .windows(window + 1)
+            .filter(|w| w[0].1 > 0.0)
+            .map(|w| {
+                let base = w[0].1;
+                let performance = Performance {
+                    curve: w.iter().map(|(time, v)| (*time, v / base)).collect(),
+                    periods_per_year: self.periods_per_year,
+                };
+                (w[window].0, f(&performance))
+            })
+            .collect()
+    }
+
+    fn mean_excess_return(&self, risk_free_rate: f64) -> f64 {
+        let returns: Vec<f64> = self.returns().into_iter().map(|(_, r)| r).collect();
+        mean(&returns) - risk_free_rate / self.periods_per_year
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::portfolio_history::TRADING_DAYS_PER_YEAR;
+    use chrono::TimeZone;
+
+    fn history(equity: &[f64], timeframe: &str) -> PortfolioHistory {
+        let timestamps: Vec<i64> = (0..equity.len() as i64)
+            .map(|i| 1614574800 + i * 86400)
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "timestamp": timestamps,
+            "equity": equity,
+            "profit_loss": vec![0; equity.len()],
+            "profit_loss_pct": vec![0; equity.len()],
+            "base_value": equity[0],
+            "timeframe": timeframe,
+        }))
+        .unwrap()
+    }
+
+    fn day(i: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1614574800 + i * 86400, 0).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn returns_and_ratios() {
+        let performance = Performance::new(&history(&[0.0, 100.0, 110.0, 99.0, 118.8], "1D"));
+        let returns: Vec<f64> = performance.returns().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(returns.len(), 3);
+        assert_close(returns[0], 0.1);
+        assert_close(returns[1], -0.1);
+        assert_close(returns[2], 0.2);
+        assert_close(performance.cumulative_return(), 0.188);
+        assert_close(
+            performance.volatility(),
+            (0.07f64 / 3.0).sqrt() * 252f64.sqrt(),
+        );
+        assert_close(
+            performance.sharpe_ratio(0.0).unwrap(),
+            (0.2 / 3.0) / (0.07f64 / 3.0).sqrt() * 252f64.sqrt(),
+        );
+        assert_close(
+            performance.sortino_ratio(0.0).unwrap(),
+            (0.2 / 3.0) / (0.01f64 / 3.0).sqrt() * 252f64.sqrt(),
+        );
+        let performance = performance.periods_per_year(1.5);
+        assert_close(performance.annualized_return(), 1.188f64.sqrt() - 1.0);
+    }
+
+    #[test]
+    fn emptied_account() {
+        // Everything is withdrawn on day 2 and deposited again on day 3.
+        let performance = Performance::new(&history(&[100.0, 110.0, 0.0, 50.0, 55.0], "1D"));
+        let returns = performance.returns();
+        assert_eq!(returns.len(), 3);
+        assert_eq!(returns[1].0, day(2));
+        assert_close(returns[1].1, -1.0);
+        assert_eq!(returns[2].0, day(4));
+        assert_close(returns[2].1, 0.1);
+        assert!(performance.volatility().is_finite());
+        assert!(performance.sharpe_ratio(0.0).unwrap().is_finite());
+        assert!(performance.sortino_ratio(0.0).unwrap().is_finite());
+        assert_close(performance.max_drawdown().unwrap().depth, 1.0);
+        let rolling = performance.rolling(1, Performance::cumulative_return);
+        assert_eq!(rolling.len(), 3);
+        assert!(rolling.iter().all(|(_, r)| r.is_finite()));
+    }
+
+    #[test]
+    fn drawdowns() {
+        let performance =
+            Performance::new(&history(&[100.0, 110.0, 99.0, 104.5, 121.0, 96.8], "1D"));
+        let drawdowns = performance.drawdowns();
+        assert_eq!(drawdowns.len(), 2);
+        assert_eq!(drawdowns[0].peak, day(1));
+        assert_eq!(drawdowns[0].trough, day(2));
+        assert_eq!(drawdowns[0].recovery, Some(day(4)));
+        assert_eq!(drawdowns[0].duration(), Some(Duration::days(3)));
+        assert_close(drawdowns[0].depth, 0.1);
+
+        let max = performance.max_drawdown().unwrap();
+        assert_eq!(max.peak, day(4));
+        assert_eq!(max.recovery, None);
+        assert_close(max.depth, 0.2);
+        assert_eq!(performance.max_drawdown_duration(), Duration::days(3));
+    }
+
+    #[test]
+    fn rolling_and_flow_adjusted() {
+        let performance = Performance::new(&history(&[100.0, 110.0, 99.0, 118.8], "1D"));
+        let rolling = performance.rolling(2, Performance::cumulative_return);
+        assert_eq!(rolling.len(), 2);
+        assert_eq!(rolling[0].0, day(2));
+        assert_close(rolling[0].1, -0.01);
+        assert_close(rolling[1].1, 0.08);
+
+        let from_returns = Performance::from_returns(
+            day(0),
+            vec![(day(1), Decimal::new(1, 1)), (day(2), Decimal::new(-1, 1))],
+            TRADING_DAYS_PER_YEAR,
+        );
+        assert_close(from_returns.cumulative_return(), -0.01);
+    }
+
+    #[test]
+    fn periods_per_year() {
+        let minutes = history(&[100.0, 101.0, 102.0], "1Min");
+        assert_close(
+            Performance::new(&minutes).periods_per_year,
+            TRADING_DAYS_PER_YEAR * 390.0,
+        );
+        assert_close(
+            Performance::new(&minutes.resample_daily()).periods_per_year,
+            TRADING_DAYS_PER_YEAR,
+        );
+    }
+}
@@ -1,10 +1,14 @@
+use crate::account_activities::ActivityType;
 use crate::utils::datetime_from_vec_timestamp;
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::America::New_York;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize, Serializer};
+use serde_with::CommaSeparator;
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use vila::{Request, RequestData};
+use vila::{Client, Request, RequestData};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 /// The resolution of the time window.
@@ -21,11 +25,36 @@ pub enum Timeframe {
     #[serde(rename = "1H")]
     /// One hour resolution
     OneHour,
+    #[serde(rename = "12H")]
+    /// Twelve hours resolution
+    TwelveHours,
     #[serde(rename = "1D")]
     /// One day resolution
     OneDay,
 }
 
+/// The number of trading days in a year, used to annualize daily statistics.
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+/// The number of minutes in a regular trading session.
+const MINUTES_PER_SESSION: f64 = 390.0;
+
+impl Timeframe {
+    /// The number of periods of this timeframe in a trading year. Intraday timeframes assume
+    /// regular sessions of 390 minutes, and twelve hour periods assume two per trading day.
+    pub fn periods_per_year(&self) -> f64 {
+        let per_day = match self {
+            Timeframe::OneMinute => MINUTES_PER_SESSION,
+            Timeframe::FiveMinutes => MINUTES_PER_SESSION / 5.0,
+            Timeframe::FifteenMinutes => MINUTES_PER_SESSION / 15.0,
+            Timeframe::OneHour => MINUTES_PER_SESSION / 60.0,
+            Timeframe::TwelveHours => 2.0,
+            Timeframe::OneDay => 1.0,
+        };
+        TRADING_DAYS_PER_YEAR * per_day
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 /// Portfolio history object
 pub struct PortfolioHistory {
@@ -33,16 +62,160 @@ pub struct PortfolioHistory {
     /// seconds
     #[serde(deserialize_with = "datetime_from_vec_timestamp")]
     pub timestamp: Vec<DateTime<Utc>>,
-    /// Equity value of the account in dollar amount as of the end of each time window
-    pub equity: Vec<Decimal>,
+    /// Equity value of the account in dollar amount as of the end of each time window. Not present
+    /// for time windows before the account was opened.
+    pub equity: Vec<Option<Decimal>>,
     /// Profit/loss in dollar from the base value
-    pub profit_loss: Vec<Decimal>,
+    pub profit_loss: Vec<Option<Decimal>>,
     /// Profit/loss in percentage from the base value
-    pub profit_loss_pct: Vec<Decimal>,
+    pub profit_loss_pct: Vec<Option<Decimal>>,
     /// Basis in dollar of the profit loss calculation
     pub base_value: Decimal,
     /// Time window size of each data element
     pub timeframe: Timeframe,
+    #[serde(default)]
+    /// Cash flows in dollar amount for each time window, by activity type. Only present when
+    /// requested with `cashflow_types`.
+    pub cashflow: HashMap<ActivityType, Vec<Option<Decimal>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A single data element of the portfolio history.
+pub struct HistoryRow {
+    /// Start of the time window
+    pub timestamp: DateTime<Utc>,
+    /// Equity value of the account as of the end of the time window
+    pub equity: Option<Decimal>,
+    /// Profit/loss in dollar from the base value
+    pub profit_loss: Option<Decimal>,
+    /// Profit/loss in percentage from the base value
+    pub profit_loss_pct: Option<Decimal>,
+}
+
+impl PortfolioHistory {
+    /// Iterate over the data elements as rows.
+    pub fn rows(&self) -> impl Iterator<Item = HistoryRow> + '_ {
+        self.timestamp
+            .iter()
+            .zip(&self.equity)
+            .zip(&self.profit_loss)
+            .zip(&self.profit_loss_pct)
+            .map(
+                |(((timestamp, equity), profit_loss), profit_loss_pct)| HistoryRow {
+                    timestamp: *timestamp,
+                    equity: *equity,
+                    profit_loss: *profit_loss,
+                    profit_loss_pct: *profit_loss_pct,
+                },
+            )
+    }
+
+    /// Resample the history to one element per trading day, using the last element of each day in
+    /// New York time. Each element is labeled with midnight in New York at the start of its day.
+    /// Cash flows are not resampled.
+    pub fn resample_daily(&self) -> PortfolioHistory {
+        let mut resampled = PortfolioHistory {
+            timestamp: Vec::new(),
+            equity: Vec::new(),
+            profit_loss: Vec::new(),
+            profit_loss_pct: Vec::new(),
+            base_value: self.base_value,
+            timeframe: Timeframe::OneDay,
+            cashflow: HashMap::new(),
+        };
+        let mut last_date = None;
+        for row in self.rows() {
+            let date = row.timestamp.with_timezone(&New_York).date_naive();
+            if last_date == Some(date) {
+                resampled.timestamp.pop();
+                resampled.equity.pop();
+                resampled.profit_loss.pop();
+                resampled.profit_loss_pct.pop();
+            }
+            last_date = Some(date);
+            resampled.timestamp.push(
+                New_York
+                    .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                    .unwrap()
+                    .with_timezone(&Utc),
+            );
+            resampled.equity.push(row.equity);
+            resampled.profit_loss.push(row.profit_loss);
+            resampled.profit_loss_pct.push(row.profit_loss_pct);
+        }
+        resampled
+    }
+
+    /// Append a later history, such as the next window of a longer period. Elements at or before
+    /// the last timestamp of this history are skipped. The profit/loss of the later history is
+    /// rebased to continue from the last profit/loss of this history, with percentages relative to
+    /// this history's base value. Cash flows are appended for the elements kept.
+    pub fn merge(&mut self, later: PortfolioHistory) {
+        let last = self.timestamp.last().copied();
+        let offset = self
+            .profit_loss
+            .iter()
+            .rev()
+            .find_map(|pl| *pl)
+            .unwrap_or_default();
+        let len = self.timestamp.len();
+        let keep: Vec<bool> = later
+            .timestamp
+            .iter()
+            .map(|t| last.map(|last| *t > last).unwrap_or(true))
+            .collect();
+        for (i, row) in later.rows().enumerate() {
+            if !keep[i] {
+                continue;
+            }
+            let profit_loss = row.profit_loss.map(|pl| pl + offset);
+            self.timestamp.push(row.timestamp);
+            self.equity.push(row.equity);
+            self.profit_loss.push(profit_loss);
+            self.profit_loss_pct
+                .push(match (profit_loss, self.base_value.is_zero()) {
+                    (Some(pl), false) => Some(pl / self.base_value),
+                    _ => row.profit_loss_pct,
+                });
+        }
+        for (activity_type, flows) in later.cashflow {
+            let series = self.cashflow.entry(activity_type).or_default();
+            series.resize(len, None);
+            series.extend(
+                flows
+                    .into_iter()
+                    .zip(&keep)
+                    .filter(|(_, keep)| **keep)
+                    .map(|(flow, _)| flow),
+            );
+        }
+        let len = self.timestamp.len();
+        self.cashflow
+            .values_mut()
+            .for_each(|series| series.resize(len, None));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// Which hours intraday data is reported for.
+pub enum IntradayReporting {
+    /// Regular market hours only
+    MarketHours,
+    /// Extended hours, from 4am to 8pm
+    ExtendedHours,
+    /// Around the clock, including overnight
+    Continuous,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+/// How the profit/loss of intraday data is reset.
+pub enum PnlReset {
+    /// Reset the profit/loss at the start of each day
+    PerDay,
+    /// Accumulate the profit/loss over the whole period
+    NoReset,
 }
 
 #[derive(Clone, Debug)]
@@ -100,6 +273,34 @@ impl Display for PeriodUnit {
     }
 }
 
+#[derive(Debug)]
+/// An error fetching a stitched portfolio history.
+pub enum StitchError {
+    /// The window is not positive.
+    InvalidWindow(Duration),
+    /// A request failed.
+    Request(vila::Error),
+}
+
+impl Display for StitchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StitchError::InvalidWindow(window) => {
+                write!(f, "window must be positive, got {}", window)
+            }
+            StitchError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StitchError {}
+
+impl From<vila::Error> for StitchError {
+    fn from(e: vila::Error) -> Self {
+        StitchError::Request(e)
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 /// Returns timeseries data about equity and profit/loss (P/L) of the account in requested
 /// timespan.
@@ -127,10 +328,27 @@ impl Display for PeriodUnit {
 /// }
 /// ```
 pub struct GetPortfolioHistory {
+    #[serde(skip_serializing_if = "Option::is_none")]
     period: Option<Period>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     timeframe: Option<Timeframe>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     date_end: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     extended_hours: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intraday_reporting: Option<IntradayReporting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pnl_reset: Option<PnlReset>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        with = "serde_with::rust::StringWithSeparator::<CommaSeparator>"
+    )]
+    cashflow_types: Vec<ActivityType>,
 }
 
 impl GetPortfolioHistory {
@@ -164,6 +382,68 @@ impl GetPortfolioHistory {
         self.extended_hours = Some(extended_hours);
         self
     }
+
+    /// The start of the data. Can't be combined with both a period and an end.
+    pub fn start(mut self, start: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// The end of the data. Can't be combined with both a period and a start.
+    pub fn end(mut self, end: DateTime<Utc>) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Which hours intraday data is reported for. Only effective for timeframes less than OneDay.
+    pub fn intraday_reporting(mut self, intraday_reporting: IntradayReporting) -> Self {
+        self.intraday_reporting = Some(intraday_reporting);
+        self
+    }
+
+    /// How the profit/loss of intraday data is reset. Only effective for timeframes less than
+    /// OneDay.
+    pub fn pnl_reset(mut self, pnl_reset: PnlReset) -> Self {
+        self.pnl_reset = Some(pnl_reset);
+        self
+    }
+
+    /// The activity types to report in the `cashflow` of the response.
+    pub fn cashflow_types(mut self, cashflow_types: &[ActivityType]) -> Self {
+        self.cashflow_types.extend_from_slice(cashflow_types);
+        self
+    }
+
+    /// Fetch the history from `start` to `end` in consecutive windows of at most `window`, and merge
+    /// them into one history. This allows fetching intraday data over periods longer than a single
+    /// request returns. The period, start, end and date end of this request are replaced for each
+    /// window. Returns an error if `window` is not positive.
+    pub async fn fetch_stitched(
+        &self,
+        client: &Client,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<Option<PortfolioHistory>, StitchError> {
+        if window <= Duration::zero() {
+            return Err(StitchError::InvalidWindow(window));
+        }
+        let mut stitched: Option<PortfolioHistory> = None;
+        let mut window_start = start;
+        while window_start < end {
+            let window_end = (window_start + window).min(end);
+            let mut request = self.clone().start(window_start).end(window_end);
+            request.period = None;
+            request.date_end = None;
+            let history = client.send(&request).await?;
+            match &mut stitched {
+                Some(stitched) => stitched.merge(history),
+                None => stitched = Some(history),
+            }
+            window_start = window_end;
+        }
+        Ok(stitched)
+    }
 }
 
 impl Request for GetPortfolioHistory {
@@ -209,6 +489,142 @@ mod test {
         client.send(&req).await.unwrap();
     }
 
+    #[test]
+    fn resample_daily() {
+        // 15:59 and 16:00 in New York on 2021-03-01, then 09:30 and 09:31 on 2021-03-02.
+        let history: PortfolioHistory = serde_json::from_str(
+            r#"{
+                "timestamp": [1614632340, 1614632400, 1614695400, 1614695460],
+                "equity": [100, 101, 102, 103],
+                "profit_loss": [0, 1, 2, 3],
+                "profit_loss_pct": [0, 0.01, 0.02, 0.03],
+                "base_value": 100,
+                "timeframe": "1Min"
+            }"#,
+        )
+        .unwrap();
+        let daily: Vec<_> = history
+            .resample_daily()
+            .rows()
+            .map(|row| (row.timestamp.timestamp(), row.equity))
+            .collect();
+        assert_eq!(
+            daily,
+            vec![
+                (1614574800, Some(Decimal::new(101, 0))),
+                (1614661200, Some(Decimal::new(103, 0)))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_portfolio_history_start_end() {
+        let _m = mock("GET", "/v2/account/portfolio/history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("timeframe".into(), "12H".into()),
+                Matcher::UrlEncoded("start".into(), "2021-01-01T00:00:00Z".into()),
+                Matcher::UrlEncoded("end".into(), "2021-01-02T00:00:00Z".into()),
+                Matcher::UrlEncoded("intraday_reporting".into(), "continuous".into()),
+                Matcher::UrlEncoded("pnl_reset".into(), "no_reset".into()),
+                Matcher::UrlEncoded("cashflow_types".into(), "CSD,CSW".into()),
+            ]))
+            .with_body(
+                r#"{
+                    "timestamp": [1609459200, 1609502400],
+                    "equity": [null, 100],
+                    "profit_loss": [null, 0],
+                    "profit_loss_pct": [null, 0],
+                    "base_value": 100,
+                    "timeframe": "12H",
+                    "cashflow": {"CSD": [0, 100]}
+                }"#,
+            )
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let req = GetPortfolioHistory::new()
+            .timeframe(Timeframe::TwelveHours)
+            .start(Utc.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap())
+            .end(Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap())
+            .intraday_reporting(IntradayReporting::Continuous)
+            .pnl_reset(PnlReset::NoReset)
+            .cashflow_types(&[ActivityType::CashDeposit, ActivityType::CashWithdrawal]);
+        let history = client.send(&req).await.unwrap();
+        assert_eq!(history.equity, vec![None, Some(Decimal::new(100, 0))]);
+        assert_eq!(
+            history.cashflow[&ActivityType::CashDeposit],
+            vec![Some(Decimal::ZERO), Some(Decimal::new(100, 0))]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_stitched() {
+        let _first = mock("GET", "/v2/account/portfolio/history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("timeframe".into(), "1H".into()),
+                Matcher::UrlEncoded("start".into(), "2021-01-04T14:00:00Z".into()),
+                Matcher::UrlEncoded("end".into(), "2021-01-04T16:00:00Z".into()),
+            ]))
+            .with_body(
+                r#"{
+                    "timestamp": [1609768800, 1609772400, 1609776000],
+                    "equity": [100, 110, 120],
+                    "profit_loss": [0, 10, 20],
+                    "profit_loss_pct": [0, 0.1, 0.2],
+                    "base_value": 100,
+                    "timeframe": "1H"
+                }"#,
+            )
+            .create();
+        let _second = mock("GET", "/v2/account/portfolio/history")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("start".into(), "2021-01-04T16:00:00Z".into()),
+                Matcher::UrlEncoded("end".into(), "2021-01-04T17:00:00Z".into()),
+            ]))
+            .with_body(
+                r#"{
+                    "timestamp": [1609776000, 1609779600],
+                    "equity": [120, 90],
+                    "profit_loss": [0, -30],
+                    "profit_loss_pct": [0, -0.25],
+                    "base_value": 120,
+                    "timeframe": "1H"
+                }"#,
+            )
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let history = GetPortfolioHistory::new()
+            .period(Period::days(1))
+            .timeframe(Timeframe::OneHour)
+            .fetch_stitched(
+                &client,
+                Utc.with_ymd_and_hms(2021, 1, 4, 14, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2021, 1, 4, 17, 0, 0).unwrap(),
+                Duration::hours(2),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let d = |n: i64| Some(Decimal::new(n, 0));
+        assert_eq!(history.timestamp.len(), 4);
+        assert_eq!(history.equity, vec![d(100), d(110), d(120), d(90)]);
+        assert_eq!(history.profit_loss, vec![d(0), d(10), d(20), d(-10)]);
+        assert_eq!(history.profit_loss_pct[3], Some(Decimal::new(-1, 1)));
+    }
+
+    #[tokio::test]
+    async fn fetch_stitched_empty_window() {
+        let client = client_with_url(&mockito::server_url(), "KEY", "SECRET");
+        let start = Utc.with_ymd_and_hms(2021, 1, 4, 14, 0, 0).unwrap();
+        let res = GetPortfolioHistory::new()
+            .fetch_stitched(&client, start, start + Duration::hours(1), Duration::zero())
+            .await;
+        assert!(matches!(res, Err(StitchError::InvalidWindow(w)) if w.is_zero()));
+    }
+
     const PORTFOLIO_HISTORY: &'static str = r#"{
 	    "timestamp": [1580826600000, 1580827500000, 1580828400000],
   		"equity": [27423.73, 27408.19, 27515.97],
@@ -1,11 +1,14 @@
-use crate::{AssetClass, Exchange, Identifier};
+use crate::orders::Order;
+use crate::{AssetClass, Exchange, Identifier, Symbol};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter::FromIterator;
 use uuid::Uuid;
-use vila::{Method, Request, RequestData};
+use vila::{Client, Method, Request, RequestData};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
 /// Denotes whether a position is long or short
 pub enum Side {
@@ -21,7 +24,7 @@ impl Default for Side {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 /// Position object
 pub struct Position {
     /// Asset ID
@@ -63,6 +66,296 @@ pub struct Position {
     pub change_today: Decimal,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+/// A change to a single position between two snapshots.
+pub enum PositionChange {
+    /// A position that did not exist in the previous snapshot.
+    Opened(Position),
+    /// A position that no longer exists in the current snapshot.
+    Closed(Position),
+    /// A position whose quantity changed while staying on the same side.
+    QtyChanged {
+        /// The position in the previous snapshot
+        previous: Position,
+        /// The position in the current snapshot
+        current: Position,
+    },
+    /// A position that went from long to short, or from short to long.
+    SideFlipped {
+        /// The position in the previous snapshot
+        previous: Position,
+        /// The position in the current snapshot
+        current: Position,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// A snapshot of positions keyed by asset ID, as returned by `GetPositions`.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     paper_client,
+///     positions::{GetPositions, PositionSet},
+/// };
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let before: PositionSet = client.send(&GetPositions).await?.into();
+///     let after: PositionSet = client.send(&GetPositions).await?.into();
+///     for change in before.diff(&after) {
+///         println!("{:?}", change);
+///     }
+///     println!("Gross exposure: {}", after.gross_exposure());
+///     Ok(())
+/// }
+/// ```
+pub struct PositionSet {
+    positions: BTreeMap<Uuid, Position>,
+}
+
+impl PositionSet {
+    /// Create an empty set of positions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the position for the given asset ID.
+    pub fn get(&self, asset_id: &Uuid) -> Option<&Position> {
+        self.positions.get(asset_id)
+    }
+
+    /// Get the position for the given symbol.
+    pub fn get_symbol(&self, symbol: &str) -> Option<&Position> {
+        self.positions.values().find(|p| p.symbol == symbol)
+    }
+
+    /// Iterate over the positions, ordered by asset ID.
+    pub fn iter(&self) -> impl Iterator<Item = &Position> {
+        self.positions.values()
+    }
+
+    /// The number of positions in the set.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Whether the set contains no positions.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Insert a position, replacing any existing position for the same asset.
+    pub fn insert(&mut self, position: Position) -> Option<Position> {
+        self.positions.insert(position.asset_id, position)
+    }
+
+    /// List the changes needed to go from this snapshot to `current`, ordered by asset ID.
+    pub fn diff(&self, current: &PositionSet) -> Vec<PositionChange> {
+        let ids: BTreeSet<&Uuid> = self
+            .positions
+            .keys()
+            .chain(current.positions.keys())
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| match (self.get(id), current.get(id)) {
+                (None, Some(current)) => Some(PositionChange::Opened(current.clone())),
+                (Some(previous), None) => Some(PositionChange::Closed(previous.clone())),
+                (Some(previous), Some(current)) if previous.side != current.side => {
+                    Some(PositionChange::SideFlipped {
+                        previous: previous.clone(),
+                        current: current.clone(),
+                    })
+                }
+                (Some(previous), Some(current)) if previous.qty != current.qty => {
+                    Some(PositionChange::QtyChanged {
+                        previous: previous.clone(),
+                        current: current.clone(),
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Total market value of all long positions.
+    pub fn long_market_value(&self) -> Decimal {
+        self.iter()
+            .filter(|p| p.side == Side::Long)
+            .map(|p| p.market_value.abs())
+            .sum()
+    }
+
+    /// Total market value of all short positions. Like `Account::short_market_value`, this is
+    /// reported as a negative number.
+    pub fn short_market_value(&self) -> Decimal {
+        -self
+            .iter()
+            .filter(|p| p.side == Side::Short)
+            .map(|p| p.market_value.abs())
+            .sum::<Decimal>()
+    }
+
+    /// Long market value plus the absolute short market value.
+    pub fn gross_exposure(&self) -> Decimal {
+        self.long_market_value() - self.short_market_value()
+    }
+
+    /// Long market value minus the absolute short market value.
+    pub fn net_exposure(&self) -> Decimal {
+        self.long_market_value() + self.short_market_value()
+    }
+}
+
+impl FromIterator<Position> for PositionSet {
+    fn from_iter<T: IntoIterator<Item = Position>>(iter: T) -> Self {
+        Self {
+            positions: iter.into_iter().map(|p| (p.asset_id, p)).collect(),
+        }
+    }
+}
+
+impl From<Vec<Position>> for PositionSet {
+    fn from(positions: Vec<Position>) -> Self {
+        positions.into_iter().collect()
+    }
+}
+
+impl IntoIterator for PositionSet {
+    type Item = Position;
+    type IntoIter = std::collections::btree_map::IntoValues<Uuid, Position>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.positions.into_values()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// A symbol that could not be closed when closing all positions.
+pub struct FailedClose {
+    /// Symbol of the position that failed to close
+    pub symbol: String,
+    /// HTTP status code reported for this symbol, or 0 if no response was received
+    pub status: u16,
+    /// Alpaca error code, if one was provided
+    pub code: Option<u64>,
+    /// Error message describing why the position could not be closed
+    pub message: String,
+}
+
+impl FailedClose {
+    fn from_error(symbol: String, error: vila::Error) -> Self {
+        let (status, body) = match &error {
+            vila::Error::ClientError(status, body) | vila::Error::ServerError(status, body) => {
+                (status.as_u16(), body.as_str())
+            }
+            _ => (0, ""),
+        };
+        let (code, message) = match serde_json::from_str(body) {
+            Ok(CloseAllPositionsBody::Error { code, message }) => (code, message),
+            _ => (None, error.to_string()),
+        };
+        Self {
+            symbol,
+            status,
+            code,
+            message,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+enum CloseAllPositionsBody {
+    Order(Box<Order>),
+    Error { code: Option<u64>, message: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CloseAllPositionsEntry {
+    symbol: String,
+    status: u16,
+    body: CloseAllPositionsBody,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(
+    from = "Vec<CloseAllPositionsEntry>",
+    into = "Vec<CloseAllPositionsEntry>"
+)]
+/// The result of closing all positions. Alpaca reports a status for each symbol, containing either
+/// the liquidation order or an error explaining why the position could not be closed.
+pub struct CloseAllPositionsResult {
+    /// Liquidation orders for the positions that were successfully closed
+    pub succeeded: Vec<Order>,
+    /// Symbols that could not be closed, along with the reason
+    pub failed: Vec<FailedClose>,
+}
+
+impl CloseAllPositionsResult {
+    /// Whether every position was successfully closed.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Attempt to close each of the failed symbols again using `ClosePosition`. Symbols that are
+    /// closed successfully are moved to `succeeded`, while the rest remain in `failed` with the
+    /// status, code and message of the latest attempt.
+    pub async fn retry_failed(mut self, client: &Client) -> Self {
+        let failed = std::mem::take(&mut self.failed);
+        for failure in failed {
+            match client
+                .send(&ClosePosition::new(Symbol::unchecked(&failure.symbol)))
+                .await
+            {
+                Ok(order) => self.succeeded.push(order),
+                Err(e) => self.failed.push(FailedClose::from_error(failure.symbol, e)),
+            }
+        }
+        self
+    }
+}
+
+impl From<Vec<CloseAllPositionsEntry>> for CloseAllPositionsResult {
+    fn from(entries: Vec<CloseAllPositionsEntry>) -> Self {
+        let mut result = Self::default();
+        for entry in entries {
+            match entry.body {
+                CloseAllPositionsBody::Order(order) => result.succeeded.push(*order),
+                CloseAllPositionsBody::Error { code, message } => result.failed.push(FailedClose {
+                    symbol: entry.symbol,
+                    status: entry.status,
+                    code,
+                    message,
+                }),
+            }
+        }
+        result
+    }
+}
+
+impl From<CloseAllPositionsResult> for Vec<CloseAllPositionsEntry> {
+    fn from(result: CloseAllPositionsResult) -> Self {
+        let succeeded = result
+            .succeeded
+            .into_iter()
+            .map(|order| CloseAllPositionsEntry {
+                symbol: order.symbol.clone(),
+                status: 200,
+                body: CloseAllPositionsBody::Order(Box::new(order)),
+            });
+        let failed = result.failed.into_iter().map(|f| CloseAllPositionsEntry {
+            symbol: f.symbol,
+            status: f.status,
+            body: CloseAllPositionsBody::Error {
+                code: f.code,
+                message: f.message,
+            },
+        });
+        succeeded.chain(failed).collect()
+    }
+}
+
 #[derive(Clone, Debug)]
 /// Retrieves a list of the account’s open positions.
 ///
@@ -99,13 +392,15 @@ impl Request for GetPositions {
 /// use apca_rest::{
 ///     paper_client,
 ///     positions::{GetPosition, Position},
+///     Symbol,
 /// };
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
-///     let position: Position = client.send(&GetPosition::new("AAPL")).await?;
+///     let position: Position = client.send(&GetPosition::new("AAPL".parse::<Symbol>()?)).await?;
 ///     Ok(())
 /// }
+/// ```
 pub struct GetPosition {
     identifier: Identifier,
 }
@@ -124,27 +419,30 @@ impl Request for GetPosition {
     type Response = Position;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/positions/{}", self.identifier).into()
+        format!("/v2/positions/{}", self.identifier.url_encoded()).into()
     }
 }
 
 #[derive(Clone, Debug, Default, Serialize)]
 /// Closes (liquidates) all of the account’s open long and short positions. A response will be
-/// provided for each order that is attempted to be cancelled. If an order is no longer cancelable,
-/// the server will respond with status 500 and reject the request.
+/// provided for each position that is attempted to be closed, containing either the liquidation
+/// order or the reason the position could not be closed.
 ///
 /// # Examples
 /// ```no_run
 /// use apca_rest::{
 ///     paper_client,
-///     positions::{CloseAllPositions, Position},
+///     positions::{CloseAllPositions, CloseAllPositionsResult},
 /// };
 /// #[tokio::main]
 /// async fn main() -> Result<(), vila::Error> {
 ///     let client = paper_client("KEY", "SECRET");
-///     let positions: Vec<Position> = client
+///     let mut result: CloseAllPositionsResult = client
 ///         .send(&CloseAllPositions::new().cancel_orders(true))
 ///         .await?;
+///     if !result.is_complete() {
+///         result = result.retry_failed(&client).await;
+///     }
 ///     Ok(())
 /// }
 pub struct CloseAllPositions {
@@ -167,7 +465,7 @@ impl CloseAllPositions {
 
 impl Request for CloseAllPositions {
     type Data = Self;
-    type Response = Vec<Position>;
+    type Response = CloseAllPositionsResult;
     const METHOD: Method = Method::DELETE;
 
     fn endpoint(&self) -> Cow<str> {
@@ -181,19 +479,23 @@ impl Request for CloseAllPositions {
 
 #[derive(Clone, Debug)]
 /// Closes (liquidates) the account’s open position for the given symbol, or asset_id. Works for both long and short positions.
+/// Returns the liquidation order.
 ///
 /// # Examples
 /// ```no_run
 /// use apca_rest::{
+///     orders::Order,
 ///     paper_client,
-///     positions::{ClosePosition, Position},
+///     positions::ClosePosition,
+///     Symbol,
 /// };
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
-///     let position: Position = client.send(&ClosePosition::new("AAPL")).await?;
+///     let order: Order = client.send(&ClosePosition::new("AAPL".parse::<Symbol>()?)).await?;
 ///     Ok(())
 /// }
+/// ```
 pub struct ClosePosition {
     identifier: Identifier,
 }
@@ -209,11 +511,11 @@ impl ClosePosition {
 
 impl Request for ClosePosition {
     type Data = ();
-    type Response = Position;
+    type Response = Order;
     const METHOD: Method = Method::DELETE;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/positions/{}", self.identifier).into()
+        format!("/v2/positions/{}", self.identifier.url_encoded()).into()
     }
 }
 
@@ -221,6 +523,7 @@ impl Request for ClosePosition {
 mod test {
     use super::*;
     use crate::client_with_url;
+    use crate::test_fixtures::{position, ORDER, POSITION};
     use mockito::mock;
 
     #[tokio::test]
@@ -247,7 +550,10 @@ mod test {
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        client.send(&GetPosition::new("AAPL")).await.unwrap();
+        client
+            .send(&GetPosition::new(Symbol::new("AAPL").unwrap()))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -273,12 +579,15 @@ mod test {
         let _m = mock("DELETE", "/v2/positions/AAPL")
             .match_header("apca-api-key-id", "APCA_API_KEY_ID")
             .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
-            .with_body(POSITION)
+            .with_body(ORDER)
             .create();
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        client.send(&ClosePosition::new("AAPL")).await.unwrap();
+        client
+            .send(&ClosePosition::new(Symbol::new("AAPL").unwrap()))
+            .await
+            .unwrap();
     }
 
     #[tokio::test]
@@ -289,7 +598,7 @@ mod test {
         )
         .match_header("apca-api-key-id", "APCA_API_KEY_ID")
         .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
-        .with_body(POSITION)
+        .with_body(ORDER)
         .create();
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
@@ -304,39 +613,135 @@ mod test {
 
     #[tokio::test]
     async fn close_all_positions() {
-        let positions = format!("[{}]", POSITION);
+        let statuses = format!(
+            r#"[{{"symbol":"AAPL","status":200,"body":{}}},{}]"#,
+            ORDER, FAILED_CLOSE
+        );
         let _m = mock("DELETE", "/v2/positions")
             .match_header("apca-api-key-id", "APCA_API_KEY_ID")
             .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
             .match_query("cancel_orders=true")
             .with_status(207)
-            .with_body(positions)
+            .with_body(statuses)
             .create();
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        client
+        let result = client
             .send(&CloseAllPositions::new().cancel_orders(true))
             .await
             .unwrap();
+        assert!(!result.is_complete());
+        assert_eq!(result.succeeded.len(), 1);
+        assert_eq!(result.succeeded[0].symbol, "AAPL");
+        assert_eq!(
+            result.failed,
+            vec![FailedClose {
+                symbol: "TSLA".into(),
+                status: 403,
+                code: Some(40310000),
+                message: "insufficient qty available for order".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_failed_closes() {
+        let _m = mock("DELETE", "/v2/positions/TSLA")
+            .match_header("apca-api-key-id", "APCA_API_KEY_ID")
+            .match_header("apca-api-secret-key", "APCA_API_SECRET_KEY")
+            .with_body(ORDER.replace("AAPL", "TSLA"))
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let result: CloseAllPositionsResult =
+            serde_json::from_str(&format!("[{}]", FAILED_CLOSE)).unwrap();
+        let result = result.retry_failed(&client).await;
+        assert!(result.is_complete());
+        assert_eq!(result.succeeded[0].symbol, "TSLA");
+    }
+
+    #[tokio::test]
+    async fn retry_failed_closes_again() {
+        let _m = mock("DELETE", "/v2/positions/NFLX")
+            .with_status(404)
+            .with_body(r#"{"code": 40410000, "message": "position not found: NFLX"}"#)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let result: CloseAllPositionsResult =
+            serde_json::from_str(&format!("[{}]", FAILED_CLOSE.replace("TSLA", "NFLX"))).unwrap();
+        let result = result.retry_failed(&client).await;
+        assert_eq!(
+            result.failed,
+            vec![FailedClose {
+                symbol: "NFLX".into(),
+                status: 404,
+                code: Some(40410000),
+                message: "position not found: NFLX".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn position_set_diff() {
+        let before: PositionSet = vec![
+            position("AAPL", 5, "120"),
+            position("MSFT", 10, "120"),
+            position("TSLA", 3, "120"),
+            position("GME", 1, "120"),
+        ]
+        .into();
+        let after: PositionSet = vec![
+            position("AAPL", 5, "120"),
+            position("MSFT", 7, "120"),
+            position("TSLA", -2, "120"),
+            position("AMZN", 1, "120"),
+        ]
+        .into();
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                PositionChange::Closed(position("GME", 1, "120")),
+                PositionChange::Opened(position("AMZN", 1, "120")),
+                PositionChange::QtyChanged {
+                    previous: position("MSFT", 10, "120"),
+                    current: position("MSFT", 7, "120"),
+                },
+                PositionChange::SideFlipped {
+                    previous: position("TSLA", 3, "120"),
+                    current: position("TSLA", -2, "120"),
+                },
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn position_set_exposure() {
+        let positions: PositionSet = vec![
+            position("AAPL", 5, "120"),
+            position("MSFT", 10, "120"),
+            position("TSLA", -2, "120"),
+        ]
+        .into();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(positions.get_symbol("TSLA").unwrap().qty, -2);
+        assert_eq!(positions.long_market_value(), Decimal::new(1800, 0));
+        assert_eq!(positions.short_market_value(), Decimal::new(-240, 0));
+        assert_eq!(positions.gross_exposure(), Decimal::new(2040, 0));
+        assert_eq!(positions.net_exposure(), Decimal::new(1560, 0));
     }
 
-    const POSITION: &'static str = r#"{
-	  "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
-	  "symbol": "AAPL",
-	  "exchange": "NASDAQ",
-	  "asset_class": "us_equity",
-	  "avg_entry_price": "100.0",
-	  "qty": "5",
-	  "side": "long",
-	  "market_value": "600.0",
-	  "cost_basis": "500.0",
-	  "unrealized_pl": "100.0",
-	  "unrealized_plpc": "0.20",
-	  "unrealized_intraday_pl": "10.0",
-	  "unrealized_intraday_plpc": "0.0084",
-	  "current_price": "120.0",
-	  "lastday_price": "119.0",
-	  "change_today": "0.0084"
+    const FAILED_CLOSE: &'static str = r#"{
+	  "symbol": "TSLA",
+	  "status": 403,
+	  "body": {
+	    "code": 40310000,
+	    "message": "insufficient qty available for order"
+	  }
 	}"#;
 }
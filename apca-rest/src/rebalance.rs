@@ -0,0 +1,422 @@
+use crate::orders::{Side, SubmitOrder, TimeInForce};
+use crate::positions::Position;
+use crate::Symbol;
+use rust_decimal::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+/// The desired holding of a single symbol.
+pub enum Target {
+    /// Fraction of the investable equity (equity minus the cash buffer) to hold, by a factor of 1.
+    Weight(Decimal),
+    /// Dollar amount to hold.
+    Notional(Decimal),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Errors that can occur when computing a rebalance.
+pub enum RebalanceError {
+    /// No price is known for the symbol. Prices are taken from the current position, or must be
+    /// provided with `Rebalance::price` for symbols that are not currently held.
+    MissingPrice(String),
+    /// The target weights add up to more than 1.
+    WeightsExceedOne(Decimal),
+}
+
+impl fmt::Display for RebalanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebalanceError::MissingPrice(symbol) => write!(f, "no price available for {}", symbol),
+            RebalanceError::WeightsExceedOne(total) => {
+                write!(
+                    f,
+                    "target weights add up to {}, which is more than 1",
+                    total
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RebalanceError {}
+
+#[derive(Clone, Debug)]
+enum Amount {
+    Qty(usize),
+    Notional(Decimal),
+}
+
+#[derive(Clone, Debug)]
+struct Trade {
+    symbol: Symbol,
+    side: Side,
+    amount: Amount,
+    value: Decimal,
+}
+
+impl From<Trade> for SubmitOrder {
+    fn from(trade: Trade) -> SubmitOrder {
+        let order = SubmitOrder::new(trade.symbol)
+            .side(trade.side)
+            .time_in_force(TimeInForce::Day);
+        match trade.amount {
+            Amount::Qty(qty) => order.qty(qty),
+            Amount::Notional(notional) => order.notional(notional),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Computes the orders needed to move a portfolio from its current positions to a set of target
+/// holdings. Symbols that are currently held but have no target are closed out.
+///
+/// Orders are returned with all sells before all buys, so that the proceeds of the sells can fund
+/// the buys. Buys are capped by the cash available after the sells, minus the cash buffer.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     account::{Account, GetAccount},
+///     paper_client,
+///     positions::{GetPositions, Position},
+///     rebalance::Rebalance,
+/// };
+/// use apca_rest::Symbol;
+/// use rust_decimal::Decimal;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let account: Account = client.send(&GetAccount).await?;
+///     let positions: Vec<Position> = client.send(&GetPositions).await?;
+///     let spy = Symbol::new("SPY")?;
+///     let tlt = Symbol::new("TLT")?;
+///     let orders = Rebalance::new()
+///         .target_weight(spy.clone(), Decimal::new(6, 1))
+///         .target_weight(tlt.clone(), Decimal::new(4, 1))
+///         .price(tlt.clone(), Decimal::new(10012, 2))
+///         .fractionable([spy, tlt])
+///         .cash_buffer(Decimal::new(1, 2))
+///         .orders(&positions, account.equity)?;
+///     for order in orders {
+///         println!("{:?}", order);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct Rebalance {
+    targets: BTreeMap<Symbol, Target>,
+    prices: HashMap<Symbol, Decimal>,
+    fractionable: HashSet<Symbol>,
+    min_trade_value: Decimal,
+    cash_buffer: Decimal,
+}
+
+impl Default for Rebalance {
+    fn default() -> Self {
+        Self {
+            targets: BTreeMap::new(),
+            prices: HashMap::new(),
+            fractionable: HashSet::new(),
+            min_trade_value: Decimal::ONE,
+            cash_buffer: Decimal::ZERO,
+        }
+    }
+}
+
+impl Rebalance {
+    /// Create a new rebalance with no targets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the given fraction of the investable equity in the symbol.
+    pub fn target_weight(mut self, symbol: Symbol, weight: Decimal) -> Self {
+        self.targets.insert(symbol, Target::Weight(weight));
+        self
+    }
+
+    /// Hold the given dollar amount in the symbol.
+    pub fn target_notional(mut self, symbol: Symbol, notional: Decimal) -> Self {
+        self.targets.insert(symbol, Target::Notional(notional));
+        self
+    }
+
+    /// Set the price used for the symbol. Overrides the current price of an existing position and
+    /// is required for symbols that are not currently held.
+    pub fn price(mut self, symbol: Symbol, price: Decimal) -> Self {
+        self.prices.insert(symbol, price);
+        self
+    }
+
+    /// Mark symbols as fractionable. Trades in fractionable symbols are sent as notional orders;
+    /// all other trades are rounded down to whole shares.
+    pub fn fractionable<T: IntoIterator<Item = Symbol>>(mut self, symbols: T) -> Self {
+        self.fractionable.extend(symbols);
+        self
+    }
+
+    /// Trades worth less than this dollar amount are skipped. Defaults to $1, the smallest
+    /// notional order Alpaca accepts.
+    pub fn min_trade_value(mut self, min_trade_value: Decimal) -> Self {
+        self.min_trade_value = min_trade_value;
+        self
+    }
+
+    /// Fraction of equity to keep in cash, by a factor of 1. Target weights are applied to the
+    /// remaining equity. Defaults to 0.
+    pub fn cash_buffer(mut self, cash_buffer: Decimal) -> Self {
+        self.cash_buffer = cash_buffer;
+        self
+    }
+
+    /// Compute the orders needed to reach the targets from the current positions, given the
+    /// account's equity.
+    pub fn orders(
+        &self,
+        positions: &[Position],
+        equity: Decimal,
+    ) -> Result<Vec<SubmitOrder>, RebalanceError> {
+        let total_weight: Decimal = self
+            .targets
+            .values()
+            .filter_map(|t| match t {
+                Target::Weight(w) => Some(*w),
+                Target::Notional(_) => None,
+            })
+            .sum();
+        if total_weight > Decimal::ONE {
+            return Err(RebalanceError::WeightsExceedOne(total_weight));
+        }
+
+        // Positions are reported by Alpaca, so their symbols need no validation.
+        let held: HashMap<Symbol, &Position> = positions
+            .iter()
+            .map(|p| (Symbol::unchecked(&p.symbol), p))
+            .collect();
+        let symbols: BTreeSet<Symbol> = self.targets.keys().chain(held.keys()).cloned().collect();
+        let investable = equity * (Decimal::ONE - self.cash_buffer);
+
+        let mut sells = Vec::new();
+        let mut buys = Vec::new();
+        for symbol in symbols {
+            let position = held.get(&symbol);
+            let current_value = position.map(|p| p.market_value).unwrap_or_default();
+            let current_qty = position.map(|p| p.qty).unwrap_or_default();
+            let target_value = match self.targets.get(&symbol) {
+                Some(Target::Weight(w)) => investable * w,
+                Some(Target::Notional(n)) => *n,
+                None => Decimal::ZERO,
+            };
+
+            let trade = if target_value.is_zero() {
+                if current_qty == 0 {
+                    continue;
+                }
+                // Close the whole position by quantity so that no residual is left behind.
+                Trade {
+                    symbol,
+                    side: if current_qty > 0 {
+                        Side::Sell
+                    } else {
+                        Side::Buy
+                    },
+                    amount: Amount::Qty(current_qty.unsigned_abs() as usize),
+                    value: current_value.abs(),
+                }
+            } else {
+                let delta = target_value - current_value;
+                let side = if delta.is_sign_negative() {
+                    Side::Sell
+                } else {
+                    Side::Buy
+                };
+                match self.size(&symbol, delta.abs(), position.copied())? {
+                    Some((amount, value)) => Trade {
+                        symbol,
+                        side,
+                        amount,
+                        value,
+                    },
+                    None => continue,
+                }
+            };
+            match trade.side {
+                Side::Sell => sells.push(trade),
+                Side::Buy => buys.push(trade),
+            }
+        }
+
+        let invested: Decimal = positions.iter().map(|p| p.market_value).sum();
+        let proceeds: Decimal = sells.iter().map(|t| t.value).sum();
+        let mut budget = equity - invested + proceeds - equity * self.cash_buffer;
+        let mut funded = Vec::with_capacity(buys.len());
+        for trade in buys {
+            if trade.value <= budget {
+                budget -= trade.value;
+                funded.push(trade);
+                continue;
+            }
+            let held = held.get(&trade.symbol).copied();
+            if let Some((amount, value)) = self.size(&trade.symbol, budget, held)? {
+                budget -= value;
+                funded.push(Trade {
+                    amount,
+                    value,
+                    ..trade
+                });
+            }
+        }
+
+        Ok(sells.into_iter().chain(funded).map(Into::into).collect())
+    }
+
+    /// Size a trade worth at most `value` dollars. Returns `None` if the trade would be smaller
+    /// than the minimum trade value.
+    fn size(
+        &self,
+        symbol: &Symbol,
+        value: Decimal,
+        position: Option<&Position>,
+    ) -> Result<Option<(Amount, Decimal)>, RebalanceError> {
+        if value < self.min_trade_value {
+            return Ok(None);
+        }
+        if self.fractionable.contains(symbol) {
+            let notional = value.round_dp_with_strategy(2, RoundingStrategy::ToZero);
+            return Ok(Some((Amount::Notional(notional), notional)));
+        }
+        let price = self
+            .prices
+            .get(symbol)
+            .copied()
+            .or_else(|| position.map(|p| p.current_price))
+            .ok_or_else(|| RebalanceError::MissingPrice(symbol.to_string()))?;
+        let qty = (value / price).trunc();
+        let value = qty * price;
+        match qty.to_usize() {
+            Some(qty) if qty > 0 && value >= self.min_trade_value => {
+                Ok(Some((Amount::Qty(qty), value)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{position, symbol};
+    use serde_json::{json, Value};
+
+    fn to_json(orders: Vec<SubmitOrder>) -> Vec<Value> {
+        orders
+            .iter()
+            .map(|o| serde_json::to_value(o).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sells_before_buys() {
+        // $10,000 equity: $6,000 in AAPL, $2,000 in MSFT and $2,000 in cash.
+        let positions = vec![position("AAPL", 60, "100"), position("MSFT", 10, "200")];
+        let orders = Rebalance::new()
+            .target_weight(symbol("AAPL"), Decimal::new(3, 1))
+            .target_weight(symbol("TSLA"), Decimal::new(5, 1))
+            .price(symbol("TSLA"), Decimal::new(250, 0))
+            .orders(&positions, Decimal::new(10000, 0))
+            .unwrap();
+        let orders = to_json(orders);
+        assert_eq!(orders.len(), 3);
+        assert_eq!(orders[0]["symbol"], "AAPL");
+        assert_eq!(orders[0]["side"], "sell");
+        assert_eq!(orders[0]["qty"], "30");
+        assert_eq!(orders[1]["symbol"], "MSFT");
+        assert_eq!(orders[1]["side"], "sell");
+        assert_eq!(orders[1]["qty"], "10");
+        assert_eq!(orders[2]["symbol"], "TSLA");
+        assert_eq!(orders[2]["side"], "buy");
+        assert_eq!(orders[2]["qty"], "20");
+    }
+
+    #[test]
+    fn fractionable_uses_notional() {
+        let orders = Rebalance::new()
+            .target_notional(symbol("SPY"), Decimal::new(123456, 2))
+            .fractionable([symbol("SPY")])
+            .orders(&[], Decimal::new(5000, 0))
+            .unwrap();
+        assert_eq!(
+            to_json(orders),
+            vec![json!({
+                "symbol": "SPY",
+                "notional": "1234.56",
+                "side": "buy",
+                "type": "market",
+                "time_in_force": "day",
+                "extended_hours": false,
+                "client_order_id": null,
+                "order_class": "simple"
+            })]
+        );
+    }
+
+    #[test]
+    fn matches_held_symbols_case_insensitively() {
+        let positions = vec![position("AAPL", 50, "100")];
+        let orders = Rebalance::new()
+            .target_notional(symbol("aapl"), Decimal::new(6000, 0))
+            .orders(&positions, Decimal::new(10000, 0))
+            .unwrap();
+        let orders = to_json(orders);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["symbol"], "AAPL");
+        assert_eq!(orders[0]["side"], "buy");
+        assert_eq!(orders[0]["qty"], "10");
+    }
+
+    #[test]
+    fn skips_small_trades() {
+        let positions = vec![position("AAPL", 50, "100")];
+        let orders = Rebalance::new()
+            .target_notional(symbol("AAPL"), Decimal::new(5050, 0))
+            .min_trade_value(Decimal::new(100, 0))
+            .orders(&positions, Decimal::new(10000, 0))
+            .unwrap();
+        assert!(orders.is_empty());
+    }
+
+    #[test]
+    fn buys_limited_by_cash_buffer() {
+        // $1,000 in cash, of which $100 must be kept back.
+        let orders = Rebalance::new()
+            .target_notional(symbol("AAPL"), Decimal::new(2000, 0))
+            .price(symbol("AAPL"), Decimal::new(100, 0))
+            .cash_buffer(Decimal::new(1, 1))
+            .orders(&[], Decimal::new(1000, 0))
+            .unwrap();
+        let orders = to_json(orders);
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0]["qty"], "9");
+    }
+
+    #[test]
+    fn errors() {
+        let res = Rebalance::new()
+            .target_weight(symbol("AAPL"), Decimal::new(6, 1))
+            .target_weight(symbol("MSFT"), Decimal::new(6, 1))
+            .orders(&[], Decimal::new(1000, 0));
+        assert_eq!(
+            res.unwrap_err(),
+            RebalanceError::WeightsExceedOne(Decimal::new(12, 1))
+        );
+        let res = Rebalance::new()
+            .target_weight(symbol("AAPL"), Decimal::ONE)
+            .orders(&[], Decimal::new(1000, 0));
+        assert_eq!(
+            res.unwrap_err(),
+            RebalanceError::MissingPrice("AAPL".to_string())
+        );
+    }
+}
@@ -0,0 +1,354 @@
+use crate::account_activities::{Activity, ActivityDetails, NonTradeActivity, Side};
+use crate::positions::Position;
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A position as implied by the replayed activities.
+pub struct ExpectedPosition {
+    /// Symbol of the asset
+    pub symbol: String,
+    /// Number of shares held. Negative for short positions.
+    pub qty: Decimal,
+    /// Average entry price of the position
+    pub avg_entry_price: Decimal,
+}
+
+impl ExpectedPosition {
+    fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            qty: Decimal::ZERO,
+            avg_entry_price: Decimal::ZERO,
+        }
+    }
+
+    /// Apply a trade of `qty` shares (negative for sells) at `price`, using average cost.
+    fn trade(&mut self, qty: Decimal, price: Decimal) {
+        let new_qty = self.qty + qty;
+        if self.qty.is_zero() || self.qty.is_sign_positive() == qty.is_sign_positive() {
+            // Opening or adding to a position.
+            let cost = self.qty * self.avg_entry_price + qty * price;
+            self.avg_entry_price = cost / new_qty;
+        } else if new_qty.is_zero() {
+            self.avg_entry_price = Decimal::ZERO;
+        } else if new_qty.is_sign_positive() != self.qty.is_sign_positive() {
+            // The trade closed the position and opened one on the other side.
+            self.avg_entry_price = price;
+        }
+        self.qty = new_qty;
+    }
+
+    /// Apply a corporate action that changes the number of shares without changing the total cost.
+    fn adjust(&mut self, qty: Decimal) {
+        let cost = self.qty * self.avg_entry_price;
+        self.qty += qty;
+        self.avg_entry_price = if self.qty.is_zero() {
+            Decimal::ZERO
+        } else {
+            cost / self.qty
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A difference between the replayed activities and the positions reported by Alpaca.
+pub enum Discrepancy {
+    /// The activities imply an open position, but Alpaca reports none.
+    MissingPosition(ExpectedPosition),
+    /// Alpaca reports a position that the activities do not account for.
+    UnexpectedPosition(Position),
+    /// The number of shares differs.
+    QtyMismatch {
+        /// Symbol of the asset
+        symbol: String,
+        /// Quantity implied by the activities
+        expected: Decimal,
+        /// Quantity reported by Alpaca
+        actual: Decimal,
+    },
+    /// The average entry price differs by more than the configured tolerance.
+    AvgEntryPriceMismatch {
+        /// Symbol of the asset
+        symbol: String,
+        /// Average entry price implied by the activities
+        expected: Decimal,
+        /// Average entry price reported by Alpaca
+        actual: Decimal,
+    },
+}
+
+#[derive(Clone, Debug)]
+/// Rebuilds the expected positions of an account by replaying its fills, stock splits and stock
+/// spinoffs, and compares them against the positions reported by Alpaca.
+///
+/// Average entry prices are computed using average cost. Splits change the number of shares while
+/// keeping the total cost of the position. Spinoffs add shares of the spun-off symbol at the
+/// activity's `per_share_amount`, or at zero cost if none is reported.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     account_activities::{Activity, GetAccountActivities},
+///     paper_client,
+///     positions::{GetPositions, Position},
+///     reconciliation::Reconciler,
+/// };
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let activities: Vec<Activity> = client.send(&GetAccountActivities::new()).await?;
+///     let positions: Vec<Position> = client.send(&GetPositions).await?;
+///     let discrepancies = Reconciler::new()
+///         .replay(&activities)
+///         .reconcile(&positions);
+///     for discrepancy in discrepancies {
+///         println!("{:?}", discrepancy);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct Reconciler {
+    positions: BTreeMap<String, ExpectedPosition>,
+    price_tolerance: Decimal,
+}
+
+impl Default for Reconciler {
+    fn default() -> Self {
+        Self {
+            positions: BTreeMap::new(),
+            price_tolerance: Decimal::new(1, 2),
+        }
+    }
+}
+
+impl Reconciler {
+    /// Create a new reconciler with no positions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The largest difference in average entry price that is not reported as a discrepancy.
+    /// Defaults to $0.01.
+    pub fn price_tolerance(mut self, price_tolerance: Decimal) -> Self {
+        self.price_tolerance = price_tolerance;
+        self
+    }
+
+    /// Replay the given activities in chronological order. Activities other than fills, stock
+    /// splits and stock spinoffs are ignored.
+    pub fn replay<'a, T: IntoIterator<Item = &'a Activity>>(mut self, activities: T) -> Self {
+        let mut activities: Vec<(DateTime<Utc>, &Activity)> = activities
+            .into_iter()
+            .map(|a| match a {
+                Activity::TradeActivity {
+                    transaction_time, ..
+                } => (*transaction_time, a),
+                Activity::NonTradeActivity(activity) => (
+                    Utc.from_utc_datetime(&activity.date.and_hms_opt(0, 0, 0).unwrap()),
+                    a,
+                ),
+            })
+            .collect();
+        activities.sort_by_key(|(time, _)| *time);
+
+        for (_, activity) in activities {
+            match activity {
+                Activity::TradeActivity {
+                    symbol,
+                    side,
+                    qty,
+                    price,
+                    ..
+                } => {
+                    let qty = match side {
+                        Side::Buy => *qty,
+                        Side::Sell | Side::SellShort => -*qty,
+                    };
+                    self.position(symbol).trade(qty, *price);
+                }
+                Activity::NonTradeActivity(NonTradeActivity {
+                    details: ActivityDetails::Split { symbol, qty, .. },
+                    ..
+                }) => self.position(symbol).adjust(*qty),
+                Activity::NonTradeActivity(NonTradeActivity {
+                    details:
+                        ActivityDetails::Spinoff {
+                            symbol,
+                            qty,
+                            per_share_amount,
+                        },
+                    ..
+                }) => self
+                    .position(symbol)
+                    .trade(*qty, per_share_amount.unwrap_or_default()),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// The positions implied by the replayed activities, ordered by symbol. Closed positions are
+    /// not included.
+    pub fn expected_positions(&self) -> impl Iterator<Item = &ExpectedPosition> {
+        self.positions.values().filter(|p| !p.qty.is_zero())
+    }
+
+    /// Compare the expected positions against the positions reported by Alpaca. Discrepancies are
+    /// ordered by symbol.
+    pub fn reconcile(&self, positions: &[Position]) -> Vec<Discrepancy> {
+        let actual: HashMap<&str, &Position> =
+            positions.iter().map(|p| (p.symbol.as_str(), p)).collect();
+        let symbols: BTreeSet<&str> = self
+            .expected_positions()
+            .map(|p| p.symbol.as_str())
+            .chain(actual.keys().copied())
+            .collect();
+
+        let mut discrepancies = Vec::new();
+        for symbol in symbols {
+            let expected = self.positions.get(symbol).filter(|p| !p.qty.is_zero());
+            match (expected, actual.get(symbol)) {
+                (Some(expected), None) => {
+                    discrepancies.push(Discrepancy::MissingPosition(expected.clone()))
+                }
+                (None, Some(actual)) => {
+                    discrepancies.push(Discrepancy::UnexpectedPosition((*actual).clone()))
+                }
+                (Some(expected), Some(actual)) => {
+                    let actual_qty = Decimal::from(actual.qty);
+                    if expected.qty != actual_qty {
+                        discrepancies.push(Discrepancy::QtyMismatch {
+                            symbol: symbol.to_string(),
+                            expected: expected.qty,
+                            actual: actual_qty,
+                        });
+                    }
+                    if (expected.avg_entry_price - actual.avg_entry_price).abs()
+                        > self.price_tolerance
+                    {
+                        discrepancies.push(Discrepancy::AvgEntryPriceMismatch {
+                            symbol: symbol.to_string(),
+                            expected: expected.avg_entry_price,
+                            actual: actual.avg_entry_price,
+                        });
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        discrepancies
+    }
+
+    fn position(&mut self, symbol: &str) -> &mut ExpectedPosition {
+        self.positions
+            .entry(symbol.to_string())
+            .or_insert_with(|| ExpectedPosition::new(symbol))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{fill, position};
+    use serde_json::json;
+
+    fn split(symbol: &str, qty: &str, date: &str) -> Activity {
+        serde_json::from_value(json!({
+            "activity_type": "SSP",
+            "id": format!("{}::{}", date, symbol),
+            "date": date,
+            "net_amount": "0",
+            "symbol": symbol,
+            "qty": qty,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn replay_fills_and_splits() {
+        // Activities are deliberately out of order; the reconciler sorts them.
+        let activities = vec![
+            fill("1", "AAPL", "sell", "10", "130", "2021-03-01T15:00:00Z"),
+            fill("2", "AAPL", "buy", "10", "100", "2021-01-04T15:00:00Z"),
+            fill("3", "AAPL", "buy", "10", "120", "2021-01-05T15:00:00Z"),
+            split("AAPL", "10", "2021-02-01"),
+            fill(
+                "4",
+                "TSLA",
+                "sell_short",
+                "5",
+                "700",
+                "2021-01-04T15:00:00Z",
+            ),
+            fill("5", "TSLA", "buy", "5", "650", "2021-01-06T15:00:00Z"),
+        ];
+        let reconciler = Reconciler::new().replay(&activities);
+        let expected: Vec<_> = reconciler.expected_positions().cloned().collect();
+        // 20 shares at $110, split to 30 shares at $73.33, then 10 sold.
+        assert_eq!(expected.len(), 1);
+        assert_eq!(expected[0].symbol, "AAPL");
+        assert_eq!(expected[0].qty, Decimal::new(20, 0));
+        assert_eq!(
+            expected[0].avg_entry_price.round_dp(2),
+            Decimal::new(7333, 2)
+        );
+    }
+
+    #[test]
+    fn flip_position() {
+        let activities = vec![
+            fill("1", "AAPL", "buy", "10", "100", "2021-01-04T15:00:00Z"),
+            fill("2", "AAPL", "sell", "15", "110", "2021-01-05T15:00:00Z"),
+        ];
+        let reconciler = Reconciler::new().replay(&activities);
+        let expected: Vec<_> = reconciler.expected_positions().cloned().collect();
+        assert_eq!(
+            expected,
+            vec![ExpectedPosition {
+                symbol: "AAPL".into(),
+                qty: Decimal::new(-5, 0),
+                avg_entry_price: Decimal::new(110, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn reconcile() {
+        let activities = vec![
+            fill("1", "AAPL", "buy", "10", "100", "2021-01-04T15:00:00Z"),
+            fill("2", "MSFT", "buy", "10", "200", "2021-01-04T15:00:00Z"),
+            fill("3", "SPY", "buy", "1", "380", "2021-01-04T15:00:00Z"),
+            fill("4", "TSLA", "buy", "2", "700", "2021-01-04T15:00:00Z"),
+        ];
+        let positions = vec![
+            position("AAPL", 10, "100.004"),
+            position("GME", 3, "40"),
+            position("MSFT", 8, "200"),
+            position("TSLA", 2, "690"),
+        ];
+        let discrepancies = Reconciler::new().replay(&activities).reconcile(&positions);
+        assert_eq!(
+            discrepancies,
+            vec![
+                Discrepancy::UnexpectedPosition(position("GME", 3, "40")),
+                Discrepancy::QtyMismatch {
+                    symbol: "MSFT".into(),
+                    expected: Decimal::new(10, 0),
+                    actual: Decimal::new(8, 0),
+                },
+                Discrepancy::MissingPosition(ExpectedPosition {
+                    symbol: "SPY".into(),
+                    qty: Decimal::ONE,
+                    avg_entry_price: Decimal::new(380, 0),
+                }),
+                Discrepancy::AvgEntryPriceMismatch {
+                    symbol: "TSLA".into(),
+                    expected: Decimal::new(700, 0),
+                    actual: Decimal::new(690, 0),
+                },
+            ]
+        );
+    }
+}
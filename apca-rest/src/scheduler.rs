@@ -0,0 +1,329 @@
+use crate::calendar::TradingCalendar;
+use crate::clock::{Clock, ClockSkew};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::{America::New_York, Tz};
+use futures::stream::{self, Stream, StreamExt};
+use vila::Client;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// An event in the trading session of a day.
+pub enum SessionEvent {
+    /// The pre-market session opens.
+    PreOpen,
+    /// The market opens.
+    Open,
+    /// The given number of minutes before the market closes.
+    BeforeClose(u32),
+    /// The market closes.
+    Close,
+    /// The post-market session closes.
+    PostClose,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A session event at the time it happens on a trading day.
+pub struct ScheduledEvent {
+    /// The event
+    pub event: SessionEvent,
+    /// The trading day the event belongs to
+    pub date: NaiveDate,
+    /// The time of the event
+    pub time: DateTime<Tz>,
+}
+
+#[derive(Clone, Debug)]
+/// Schedules session events from the trading calendar, so jobs can wait for them instead of
+/// polling the clock.
+///
+/// Times are taken from the calendar, and from the most recent clock passed to `sync` for the
+/// days it covers, so that a close that is not in the cached calendar is still respected. Timers
+/// are measured against the server's time, estimated by adding the offset between the server and
+/// local clocks to the local time.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     calendar::{GetCalendar, TradingCalendar},
+///     paper_client,
+///     scheduler::{SessionEvent, SessionScheduler},
+/// };
+/// use futures::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let calendar = TradingCalendar::fetch(&client, &GetCalendar::new()).await?;
+///     let mut scheduler = SessionScheduler::new(calendar)
+///         .event(SessionEvent::Open)
+///         .event(SessionEvent::BeforeClose(5));
+///     scheduler.sync_clock(&client).await?;
+///     let mut events = Box::pin(scheduler.stream());
+///     while let Some(event) = events.next().await {
+///         println!("{:?} at {}", event.event, event.time);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct SessionScheduler {
+    calendar: TradingCalendar,
+    events: Vec<SessionEvent>,
+    clock: Option<Clock>,
+    offset: Duration,
+}
+
+impl SessionScheduler {
+    /// Create a scheduler with no events.
+    pub fn new(calendar: TradingCalendar) -> Self {
+        Self {
+            calendar,
+            events: Vec::new(),
+            clock: None,
+            offset: Duration::zero(),
+        }
+    }
+
+    /// Schedule the event on every trading day.
+    pub fn event(mut self, event: SessionEvent) -> Self {
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+        self
+    }
+
+    /// The offset of the server's clock from the local clock, added to the local time.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Use the skew estimated by a `SkewEstimator` for the offset of the server's clock.
+    pub fn skew(self, skew: &ClockSkew) -> Self {
+        self.offset(skew.offset)
+    }
+
+    /// Use the clock for the times of the next open and close, and estimate the offset of the
+    /// server's clock from its timestamp, ignoring the latency of the request it was fetched with.
+    pub fn sync(&mut self, clock: Clock) {
+        self.offset = clock.timestamp - Utc::now();
+        self.clock = Some(clock);
+    }
+
+    /// Fetch the clock, using it for the times of the next open and close, and estimate the
+    /// offset of the server's clock from the round trip.
+    pub async fn sync_clock(&mut self, client: &Client) -> Result<(), vila::Error> {
+        let (clock, skew) = ClockSkew::measure(client).await?;
+        self.offset = skew.offset;
+        self.clock = Some(clock);
+        Ok(())
+    }
+
+    /// The current time on the server.
+    pub fn now(&self) -> DateTime<Tz> {
+        (Utc::now() + self.offset).with_timezone(&New_York)
+    }
+
+    /// The events on the date, in the order they happen. Days the market is closed have none.
+    pub fn schedule(&self, date: NaiveDate) -> Vec<ScheduledEvent> {
+        let day = match self.calendar.get(date) {
+            Some(day) => day,
+            None => return Vec::new(),
+        };
+        let mut open = day.market_open();
+        let mut close = day.market_close();
+        if let Some(clock) = &self.clock {
+            if new_york_date(&clock.next_open) == date {
+                open = clock.next_open.with_timezone(&New_York);
+            }
+            if new_york_date(&clock.next_close) == date {
+                close = clock.next_close.with_timezone(&New_York);
+            }
+        }
+        let mut events: Vec<ScheduledEvent> = self
+            .events
+            .iter()
+            .filter_map(|&event| {
+                let time = match event {
+                    SessionEvent::PreOpen => day.pre_market()?.0,
+                    SessionEvent::Open => open,
+                    SessionEvent::BeforeClose(minutes) => close - Duration::minutes(minutes.into()),
+                    SessionEvent::Close => close,
+                    SessionEvent::PostClose => day.post_market()?.1,
+                };
+                Some(ScheduledEvent { event, date, time })
+            })
+            .collect();
+        events.sort_by_key(|e| e.time);
+        events
+    }
+
+    /// The events after the given time, in the order they happen.
+    pub fn upcoming(&self, after: DateTime<Tz>) -> impl Iterator<Item = ScheduledEvent> + '_ {
+        let start = after.date_naive();
+        self.calendar
+            .days()
+            .iter()
+            .skip_while(move |day| day.date < start)
+            .flat_map(move |day| self.schedule(day.date))
+            .filter(move |e| e.time > after)
+    }
+
+    /// The next event to happen.
+    pub fn next_event(&self) -> Option<ScheduledEvent> {
+        self.upcoming(self.now()).next()
+    }
+
+    /// Wait until the server's time reaches `time`. The remaining time is measured once and then
+    /// slept on the runtime's timer, so a paused runtime clock controls it.
+    pub async fn sleep_until(&self, time: DateTime<Tz>) {
+        if let Ok(remaining) = (time - self.now()).to_std() {
+            tokio::time::sleep(remaining).await;
+        }
+    }
+
+    /// Yield each event when it happens, until the end of the calendar. Events that happen at the
+    /// same time are yielded together. If the stream is not polled in time, late events are
+    /// yielded as soon as it is.
+    pub fn stream(&self) -> impl Stream<Item = ScheduledEvent> + '_ {
+        stream::unfold(self.now(), move |after| async move {
+            let mut upcoming = self.upcoming(after).peekable();
+            let first = upcoming.next()?;
+            let mut events = vec![first.clone()];
+            while let Some(event) = upcoming.next_if(|e| e.time == first.time) {
+                events.push(event);
+            }
+            self.sleep_until(first.time).await;
+            Some((stream::iter(events), first.time))
+        })
+        .flatten()
+    }
+}
+
+fn new_york_date(time: &DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&New_York).date_naive()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client_with_url;
+    use crate::test_fixtures::calendar;
+    use futures::FutureExt;
+    use mockito::mock;
+
+    fn scheduler() -> SessionScheduler {
+        SessionScheduler::new(calendar())
+            .event(SessionEvent::PostClose)
+            .event(SessionEvent::Close)
+            .event(SessionEvent::BeforeClose(5))
+            .event(SessionEvent::Open)
+            .event(SessionEvent::PreOpen)
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn times(events: &[ScheduledEvent]) -> Vec<(SessionEvent, String)> {
+        events
+            .iter()
+            .map(|e| (e.event, e.time.format("%m-%d %H:%M").to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn schedule() {
+        let scheduler = scheduler();
+        assert_eq!(
+            times(&scheduler.schedule(date("2021-11-26"))),
+            vec![
+                (SessionEvent::PreOpen, "11-26 04:00".into()),
+                (SessionEvent::Open, "11-26 09:30".into()),
+                (SessionEvent::BeforeClose(5), "11-26 12:55".into()),
+                (SessionEvent::Close, "11-26 13:00".into()),
+                (SessionEvent::PostClose, "11-26 17:00".into()),
+            ]
+        );
+        assert!(scheduler.schedule(date("2021-11-25")).is_empty());
+    }
+
+    #[test]
+    fn clock_overrides_calendar() {
+        let mut scheduler = scheduler();
+        scheduler.sync(Clock {
+            timestamp: "2021-11-29T15:00:00Z".parse().unwrap(),
+            is_open: true,
+            next_open: "2021-11-30T14:30:00Z".parse().unwrap(),
+            next_close: "2021-11-29T17:00:00Z".parse().unwrap(),
+        });
+        let schedule = scheduler.schedule(date("2021-11-29"));
+        assert_eq!(
+            times(&schedule[1..4]),
+            vec![
+                (SessionEvent::Open, "11-29 09:30".into()),
+                (SessionEvent::BeforeClose(5), "11-29 11:55".into()),
+                (SessionEvent::Close, "11-29 12:00".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn upcoming() {
+        let scheduler = scheduler();
+        let after = scheduler.schedule(date("2021-11-24"))[3].time;
+        let upcoming: Vec<_> = scheduler.upcoming(after).take(3).collect();
+        assert_eq!(
+            times(&upcoming),
+            vec![
+                (SessionEvent::PostClose, "11-24 20:00".into()),
+                (SessionEvent::PreOpen, "11-26 04:00".into()),
+                (SessionEvent::Open, "11-26 09:30".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        tokio::time::pause();
+        let close = scheduler().schedule(date("2021-11-26"))[3].time;
+        let scheduler = scheduler()
+            .event(SessionEvent::BeforeClose(0))
+            .offset(close.with_timezone(&Utc) - Utc::now() - Duration::minutes(1));
+        let mut events = Box::pin(scheduler.stream().take(2).collect::<Vec<_>>());
+        assert_eq!((&mut events).now_or_never(), None);
+
+        tokio::time::advance(std::time::Duration::from_secs(59)).await;
+        assert_eq!((&mut events).now_or_never(), None);
+
+        tokio::time::advance(std::time::Duration::from_secs(2)).await;
+        let events = events.now_or_never().unwrap();
+        assert_eq!(
+            times(&events),
+            vec![
+                (SessionEvent::Close, "11-26 13:00".into()),
+                (SessionEvent::BeforeClose(0), "11-26 13:00".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_clock() {
+        let _m = mock("GET", "/v2/clock")
+            .with_body(
+                r#"{
+                    "timestamp": "2021-11-26T12:00:00-05:00",
+                    "is_open": true,
+                    "next_open": "2021-11-29T09:30:00-05:00",
+                    "next_close": "2021-11-26T13:00:00-05:00"
+                }"#,
+            )
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let mut scheduler = scheduler();
+        scheduler.sync_clock(&client).await.unwrap();
+        let next = scheduler.next_event().unwrap();
+        assert_eq!(next.event, SessionEvent::BeforeClose(5));
+        assert_eq!(next.date, date("2021-11-26"));
+    }
+}
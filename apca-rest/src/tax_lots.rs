@@ -0,0 +1,624 @@
+use crate::account_activities::{Activity, ActivityDetails, NonTradeActivity, Side as FillSide};
+use crate::positions::Side;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::America::New_York;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// The number of days before and after a loss in which buying the same security makes it a wash
+/// sale.
+const WASH_SALE_DAYS: i64 = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The method used to choose which lots are closed by a sale.
+pub enum LotMethod {
+    /// First in, first out
+    Fifo,
+    /// Last in, first out
+    Lifo,
+    /// Highest cost lots first. For short lots, the lots sold at the lowest price are closed first.
+    Hifo,
+    /// Lots designated for each order with `TaxLots::designate`, falling back to FIFO for orders
+    /// without designations or once the designated lots are used up.
+    SpecificId,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The holding period of a realized gain or loss.
+pub enum Term {
+    /// Held for one year or less. Gains on short sales are always short-term.
+    ShortTerm,
+    /// Held for more than one year.
+    LongTerm,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// An open tax lot.
+pub struct Lot {
+    /// The id of the fill that opened the lot.
+    pub id: String,
+    /// Symbol of the lot
+    pub symbol: String,
+    /// Whether the lot is long or short
+    pub side: Side,
+    /// Number of shares in the lot
+    pub qty: Decimal,
+    /// Cost basis per share for long lots, or sale price per share for short lots, adjusted for
+    /// splits and disallowed wash sale losses.
+    pub price: Decimal,
+    /// The start of the holding period, adjusted for wash sales.
+    pub acquired: NaiveDate,
+    /// Whether the lot replaced shares sold at a loss, and carries the disallowed loss.
+    pub wash_sale_replacement: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A gain or loss realized by closing all or part of a lot.
+pub struct RealizedGain {
+    /// Symbol of the lot
+    pub symbol: String,
+    /// The id of the fill that opened the lot
+    pub lot_id: String,
+    /// Whether the lot was long or short
+    pub side: Side,
+    /// Number of shares closed
+    pub qty: Decimal,
+    /// The start of the holding period
+    pub acquired: NaiveDate,
+    /// The date the lot was closed
+    pub disposed: NaiveDate,
+    /// Total proceeds of the sale
+    pub proceeds: Decimal,
+    /// Total cost basis of the shares closed
+    pub cost_basis: Decimal,
+    /// The holding period of the gain
+    pub term: Term,
+    /// The part of the loss that is disallowed because the shares were replaced within 30 days.
+    pub wash_sale_disallowed: Decimal,
+}
+
+impl RealizedGain {
+    /// Proceeds less cost basis.
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost_basis
+    }
+
+    /// The gain or loss to report, after adding back any disallowed wash sale loss.
+    pub fn reportable_gain(&self) -> Decimal {
+        self.gain() + self.wash_sale_disallowed
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Realized gains and losses for a symbol in a calendar year.
+pub struct GainSummary {
+    /// Symbol
+    pub symbol: String,
+    /// Calendar year the gains were realized in
+    pub year: i32,
+    /// Net short-term gain or loss, after wash sale adjustments
+    pub short_term: Decimal,
+    /// Net long-term gain or loss, after wash sale adjustments
+    pub long_term: Decimal,
+    /// Total loss disallowed by wash sales
+    pub wash_sale_disallowed: Decimal,
+}
+
+#[derive(Clone, Debug)]
+struct PendingWashSale {
+    realized: usize,
+    disposed: NaiveDate,
+    qty: Decimal,
+    loss_per_share: Decimal,
+    held: Duration,
+}
+
+#[derive(Clone, Debug)]
+/// Builds tax lots from fills and computes the realized gains and losses of closing them.
+///
+/// Fills are replayed in order of execution, with trade dates in New York time. Buys cover short
+/// lots before opening long lots, and sells close long lots before opening short lots. Stock splits
+/// adjust the quantity and price of the open lots. Losses on long lots are disallowed as wash sales
+/// when the same symbol is bought within 30 days before or after the sale; the disallowed loss is
+/// added to the basis of the replacement shares and their holding period is extended by that of
+/// the shares sold.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     account_activities::{Activity, GetAccountActivities},
+///     paper_client,
+///     tax_lots::{LotMethod, TaxLots},
+///     Sort,
+/// };
+/// use futures::TryStreamExt;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), vila::Error> {
+///     let client = paper_client("KEY", "SECRET");
+///     let pages: Vec<Vec<Activity>> = client
+///         .send_paginated(&GetAccountActivities::new().sort(Sort::Ascending))
+///         .try_collect()
+///         .await?;
+///     let lots = TaxLots::new(LotMethod::Fifo).replay(pages.iter().flatten());
+///     for summary in lots.report() {
+///         println!("{:?}", summary);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct TaxLots {
+    method: LotMethod,
+    designations: HashMap<Uuid, Vec<String>>,
+    lots: BTreeMap<String, Vec<Lot>>,
+    realized: Vec<RealizedGain>,
+    pending: HashMap<String, Vec<PendingWashSale>>,
+}
+
+impl TaxLots {
+    /// Create an empty set of lots, closed using the given method.
+    pub fn new(method: LotMethod) -> Self {
+        Self {
+            method,
+            designations: HashMap::new(),
+            lots: BTreeMap::new(),
+            realized: Vec::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Designate the lots, by the id of the fill that opened them, to close with the given order
+    /// when using `LotMethod::SpecificId`.
+    pub fn designate<T: ToString>(mut self, order_id: Uuid, lot_ids: &[T]) -> Self {
+        self.designations
+            .insert(order_id, lot_ids.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    /// Replay the fills and stock splits. Other activities are ignored.
+    pub fn replay<'a, T: IntoIterator<Item = &'a Activity>>(mut self, activities: T) -> Self {
+        let mut activities: Vec<(NaiveDate, Option<DateTime<Utc>>, &Activity)> = activities
+            .into_iter()
+            .map(|a| match a {
+                Activity::TradeActivity {
+                    transaction_time, ..
+                } => (trade_date(transaction_time), Some(*transaction_time), a),
+                Activity::NonTradeActivity(activity) => (activity.date, None, a),
+            })
+            .collect();
+        // Splits take effect before the fills made on the same day.
+        activities.sort_by_key(|(date, time, _)| (*date, *time));
+
+        for (date, _, activity) in activities {
+            match activity {
+                Activity::TradeActivity {
+                    id,
+                    symbol,
+                    side,
+                    qty,
+                    price,
+                    order_id,
+                    ..
+                } => {
+                    let qty = match side {
+                        FillSide::Buy => *qty,
+                        FillSide::Sell | FillSide::SellShort => -*qty,
+                    };
+                    self.fill(id, symbol, qty, *price, date, order_id);
+                }
+                Activity::NonTradeActivity(NonTradeActivity {
+                    details: ActivityDetails::Split { symbol, qty, .. },
+                    ..
+                }) => self.split(symbol, *qty),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// The lots still open, ordered by symbol and then by the order they were opened in.
+    pub fn open_lots(&self) -> impl Iterator<Item = &Lot> {
+        self.lots.values().flatten()
+    }
+
+    /// The gains and losses realized so far, in the order they were realized.
+    pub fn realized(&self) -> &[RealizedGain] {
+        &self.realized
+    }
+
+    /// Realized gains and losses per symbol and year, split into short-term and long-term, ordered
+    /// by symbol and then by year.
+    pub fn report(&self) -> Vec<GainSummary> {
+        let mut summaries: BTreeMap<(&str, i32), GainSummary> = BTreeMap::new();
+        for gain in &self.realized {
+            let year = gain.disposed.year();
+            let summary = summaries
+                .entry((&gain.symbol, year))
+                .or_insert_with(|| GainSummary {
+                    symbol: gain.symbol.clone(),
+                    year,
+                    short_term: Decimal::ZERO,
+                    long_term: Decimal::ZERO,
+                    wash_sale_disallowed: Decimal::ZERO,
+                });
+            match gain.term {
+                Term::ShortTerm => summary.short_term += gain.reportable_gain(),
+                Term::LongTerm => summary.long_term += gain.reportable_gain(),
+            }
+            summary.wash_sale_disallowed += gain.wash_sale_disallowed;
+        }
+        summaries.into_values().collect()
+    }
+
+    fn fill(
+        &mut self,
+        id: &str,
+        symbol: &str,
+        qty: Decimal,
+        price: Decimal,
+        date: NaiveDate,
+        order_id: &Uuid,
+    ) {
+        let opening_side = if qty.is_sign_positive() {
+            Side::Long
+        } else {
+            Side::Short
+        };
+        let mut remaining = qty.abs();
+        let lots = self.lots.entry(symbol.to_string()).or_default();
+
+        // Close lots on the other side first.
+        if lots
+            .first()
+            .map(|l| l.side != opening_side)
+            .unwrap_or(false)
+        {
+            let designated = self
+                .designations
+                .get(order_id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut losses = Vec::new();
+            for i in close_order(self.method, lots, designated) {
+                if remaining.is_zero() {
+                    break;
+                }
+                let lot = &mut lots[i];
+                let closed = remaining.min(lot.qty);
+                remaining -= closed;
+                lot.qty -= closed;
+                let (proceeds, cost_basis, term) = match lot.side {
+                    Side::Long => (
+                        price * closed,
+                        lot.price * closed,
+                        holding_term(lot.acquired, date),
+                    ),
+                    Side::Short => (lot.price * closed, price * closed, Term::ShortTerm),
+                };
+                self.realized.push(RealizedGain {
+                    symbol: symbol.to_string(),
+                    lot_id: lot.id.clone(),
+                    side: lot.side.clone(),
+                    qty: closed,
+                    acquired: lot.acquired,
+                    disposed: date,
+                    proceeds,
+                    cost_basis,
+                    term,
+                    wash_sale_disallowed: Decimal::ZERO,
+                });
+                if lot.side == Side::Long && proceeds < cost_basis {
+                    losses.push(PendingWashSale {
+                        realized: self.realized.len() - 1,
+                        disposed: date,
+                        qty: closed,
+                        loss_per_share: (cost_basis - proceeds) / closed,
+                        held: date - lot.acquired,
+                    });
+                }
+            }
+            lots.retain(|l| !l.qty.is_zero());
+
+            // Shares bought in the 30 days before the loss replace the shares sold.
+            for mut loss in losses {
+                let mut i = 0;
+                while i < lots.len() && !loss.qty.is_zero() {
+                    let lot = &lots[i];
+                    if !lot.wash_sale_replacement
+                        && lot.id != self.realized[loss.realized].lot_id
+                        && date - lot.acquired <= Duration::days(WASH_SALE_DAYS)
+                    {
+                        replace(lots, i, &mut loss, &mut self.realized);
+                    }
+                    i += 1;
+                }
+                if !loss.qty.is_zero() {
+                    self.pending
+                        .entry(symbol.to_string())
+                        .or_default()
+                        .push(loss);
+                }
+            }
+        }
+
+        if !remaining.is_zero() {
+            lots.push(Lot {
+                id: id.to_string(),
+                symbol: symbol.to_string(),
+                side: opening_side.clone(),
+                qty: remaining,
+                price,
+                acquired: date,
+                wash_sale_replacement: false,
+            });
+
+            // Shares bought in the 30 days after a loss replace the shares sold.
+            if opening_side == Side::Long {
+                let pending = self.pending.entry(symbol.to_string()).or_default();
+                pending.retain(|p| date - p.disposed <= Duration::days(WASH_SALE_DAYS));
+                let start = lots.len() - 1;
+                for (n, loss) in pending.iter_mut().enumerate() {
+                    let i = start + n;
+                    if i >= lots.len() || lots[i].wash_sale_replacement {
+                        break;
+                    }
+                    replace(lots, i, loss, &mut self.realized);
+                }
+                pending.retain(|p| !p.qty.is_zero());
+            }
+        }
+    }
+
+    /// Adjust the open lots for a split that changed the number of shares held by `qty`.
+    fn split(&mut self, symbol: &str, qty: Decimal) {
+        let lots = match self.lots.get_mut(symbol) {
+            Some(lots) if !lots.is_empty() => lots,
+            _ => return,
+        };
+        let held: Decimal = lots.iter().map(|l| l.qty).sum();
+        let ratio = (held + qty) / held;
+        if !ratio.is_sign_positive() || ratio.is_zero() {
+            return;
+        }
+        for lot in lots.iter_mut() {
+            lot.qty *= ratio;
+            lot.price /= ratio;
+        }
+        for loss in self.pending.get_mut(symbol).into_iter().flatten() {
+            loss.qty *= ratio;
+            loss.loss_per_share /= ratio;
+        }
+    }
+}
+
+/// The order in which the lots are closed.
+fn close_order(method: LotMethod, lots: &[Lot], designated: &[String]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..lots.len()).collect();
+    match method {
+        LotMethod::Fifo => {}
+        LotMethod::Lifo => order.reverse(),
+        LotMethod::Hifo => order.sort_by(|a, b| match lots[*a].side {
+            Side::Long => lots[*b].price.cmp(&lots[*a].price),
+            Side::Short => lots[*a].price.cmp(&lots[*b].price),
+        }),
+        LotMethod::SpecificId => order.sort_by_key(|i| {
+            designated
+                .iter()
+                .position(|id| *id == lots[*i].id)
+                .unwrap_or(designated.len())
+        }),
+    }
+    order
+}
+
+/// Mark up to `loss.qty` shares of the lot at `index` as replacing shares sold at a loss, splitting
+/// the lot if only part of it is needed.
+fn replace(
+    lots: &mut Vec<Lot>,
+    index: usize,
+    loss: &mut PendingWashSale,
+    realized: &mut [RealizedGain],
+) {
+    let qty = loss.qty.min(lots[index].qty);
+    if qty < lots[index].qty {
+        let mut rest = lots[index].clone();
+        rest.qty -= qty;
+        lots[index].qty = qty;
+        lots.insert(index + 1, rest);
+    }
+    let lot = &mut lots[index];
+    lot.price += loss.loss_per_share;
+    lot.acquired -= loss.held;
+    lot.wash_sale_replacement = true;
+    realized[loss.realized].wash_sale_disallowed += loss.loss_per_share * qty;
+    loss.qty -= qty;
+}
+
+fn trade_date(time: &DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&New_York).date_naive()
+}
+
+fn holding_term(acquired: NaiveDate, disposed: NaiveDate) -> Term {
+    let year = acquired.year() + 1;
+    let anniversary = acquired
+        .with_year(year)
+        .or_else(|| NaiveDate::from_ymd_opt(year, 2, 28));
+    match anniversary {
+        Some(anniversary) if disposed > anniversary => Term::LongTerm,
+        _ => Term::ShortTerm,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::{fill, uuid};
+    use serde_json::json;
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn lots() -> Vec<Activity> {
+        vec![
+            fill("lot1", "AAPL", "buy", "10", "100", "2020-01-02T15:00:00Z"),
+            fill("lot2", "AAPL", "buy", "10", "150", "2020-06-01T15:00:00Z"),
+            fill("lot3", "AAPL", "buy", "10", "120", "2020-09-01T15:00:00Z"),
+            fill("sale", "AAPL", "sell", "10", "130", "2021-03-01T15:00:00Z"),
+        ]
+    }
+
+    #[test]
+    fn lot_methods() {
+        let closed = |lots: TaxLots| {
+            let gain = &lots.realized()[0];
+            (gain.lot_id.clone(), gain.gain(), gain.term)
+        };
+        assert_eq!(
+            closed(TaxLots::new(LotMethod::Fifo).replay(&lots())),
+            ("lot1".into(), d("300"), Term::LongTerm)
+        );
+        assert_eq!(
+            closed(TaxLots::new(LotMethod::Lifo).replay(&lots())),
+            ("lot3".into(), d("100"), Term::ShortTerm)
+        );
+        assert_eq!(
+            closed(TaxLots::new(LotMethod::Hifo).replay(&lots())),
+            ("lot2".into(), d("-200"), Term::ShortTerm)
+        );
+        let lots = TaxLots::new(LotMethod::SpecificId)
+            .designate(uuid("sale"), &["lot3"])
+            .replay(&lots());
+        assert_eq!(
+            closed(lots.clone()),
+            ("lot3".into(), d("100"), Term::ShortTerm)
+        );
+        assert_eq!(
+            lots.open_lots().map(|l| l.id.as_str()).collect::<Vec<_>>(),
+            vec!["lot1", "lot2"]
+        );
+    }
+
+    #[test]
+    fn short_sales_and_splits() {
+        let split: Activity = serde_json::from_value(json!({
+            "activity_type": "SSP",
+            "id": "split",
+            "date": "2021-02-01",
+            "net_amount": "0",
+            "symbol": "AAPL",
+            "qty": "-10",
+        }))
+        .unwrap();
+        let activities = vec![
+            fill(
+                "short",
+                "AAPL",
+                "sell_short",
+                "20",
+                "50",
+                "2021-01-04T15:00:00Z",
+            ),
+            split,
+            fill("cover", "AAPL", "buy", "5", "20", "2021-02-02T15:00:00Z"),
+            fill("flip", "AAPL", "buy", "10", "30", "2021-02-03T15:00:00Z"),
+        ];
+        let lots = TaxLots::new(LotMethod::Fifo).replay(&activities);
+        // A 1:2 reverse split turns 20 shares short at $50 into 10 shares short at $100.
+        let realized: Vec<_> = lots
+            .realized()
+            .iter()
+            .map(|g| (g.qty, g.gain(), g.side.clone(), g.term))
+            .collect();
+        assert_eq!(
+            realized,
+            vec![
+                (d("5"), d("400"), Side::Short, Term::ShortTerm),
+                (d("5"), d("350"), Side::Short, Term::ShortTerm),
+            ]
+        );
+        let open: Vec<_> = lots.open_lots().cloned().collect();
+        assert_eq!(
+            open,
+            vec![Lot {
+                id: "flip".into(),
+                symbol: "AAPL".into(),
+                side: Side::Long,
+                qty: d("5"),
+                price: d("30"),
+                acquired: date("2021-02-03"),
+                wash_sale_replacement: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn wash_sale_after_loss() {
+        let activities = vec![
+            fill("lot1", "AAPL", "buy", "10", "100", "2021-01-04T15:00:00Z"),
+            fill("loss", "AAPL", "sell", "10", "80", "2021-02-01T15:00:00Z"),
+            fill("lot2", "AAPL", "buy", "10", "85", "2021-02-10T15:00:00Z"),
+            fill("gain", "AAPL", "sell", "10", "110", "2021-03-01T15:00:00Z"),
+        ];
+        let lots = TaxLots::new(LotMethod::Fifo).replay(&activities);
+        let realized = lots.realized();
+        assert_eq!(realized[0].gain(), d("-200"));
+        assert_eq!(realized[0].wash_sale_disallowed, d("200"));
+        assert_eq!(realized[0].reportable_gain(), Decimal::ZERO);
+        // The replacement lot's basis is $105, held since 28 days before it was bought.
+        assert_eq!(realized[1].cost_basis, d("1050"));
+        assert_eq!(realized[1].acquired, date("2021-01-13"));
+        assert_eq!(
+            lots.report(),
+            vec![GainSummary {
+                symbol: "AAPL".into(),
+                year: 2021,
+                short_term: d("50"),
+                long_term: Decimal::ZERO,
+                wash_sale_disallowed: d("200"),
+            }]
+        );
+    }
+
+    #[test]
+    fn wash_sale_before_loss() {
+        let activities = vec![
+            fill("lot1", "AAPL", "buy", "10", "100", "2021-01-04T15:00:00Z"),
+            fill("lot2", "AAPL", "buy", "5", "90", "2021-01-20T15:00:00Z"),
+            fill("loss", "AAPL", "sell", "10", "80", "2021-01-25T15:00:00Z"),
+            // More than 30 days after the loss, so not a replacement.
+            fill("lot3", "AAPL", "buy", "5", "90", "2021-03-01T15:00:00Z"),
+        ];
+        let lots = TaxLots::new(LotMethod::Fifo).replay(&activities);
+        assert_eq!(lots.realized()[0].wash_sale_disallowed, d("100"));
+        assert_eq!(lots.realized()[0].reportable_gain(), d("-100"));
+        let open: Vec<_> = lots
+            .open_lots()
+            .map(|l| (l.id.as_str(), l.price, l.acquired, l.wash_sale_replacement))
+            .collect();
+        assert_eq!(
+            open,
+            vec![
+                ("lot2", d("110"), date("2020-12-30"), true),
+                ("lot3", d("90"), date("2021-03-01"), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn holding_period() {
+        assert_eq!(
+            holding_term(date("2020-01-02"), date("2021-01-02")),
+            Term::ShortTerm
+        );
+        assert_eq!(
+            holding_term(date("2020-01-02"), date("2021-01-03")),
+            Term::LongTerm
+        );
+    }
+}
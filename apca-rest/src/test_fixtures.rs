@@ -0,0 +1,212 @@
+use crate::account_activities::Activity;
+use crate::assets::Asset;
+use crate::calendar::{Calendar, TradingCalendar};
+use crate::positions::Position;
+use crate::Symbol;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+pub(crate) const ACCOUNT: &str = r#"{
+	    "account_blocked": false,
+		"account_number": "010203ABCD",
+		"buying_power": "262113.632",
+		"cash": "-23140.2",
+		"created_at": "2019-06-12T22:47:07.99658Z",
+		"currency": "USD",
+		"daytrade_count": 0,
+		"daytrading_buying_power": "262113.632",
+		"equity": "103820.56",
+		"id": "e6fe16f3-64a4-4921-8928-cadf02f92f98",
+		"initial_margin": "63480.38",
+		"last_equity": "103529.24",
+		"last_maintenance_margin": "38000.832",
+		"long_market_value": "126960.76",
+		"maintenance_margin": "38088.228",
+		"multiplier": "4",
+		"pattern_day_trader": false,
+		"regt_buying_power": "80680.36",
+		"short_market_value": "0",
+		"shorting_enabled": true,
+		"sma": "0",
+		"status": "ACTIVE",
+		"trade_suspended_by_user": false,
+		"trading_blocked": false,
+		"transfers_blocked": false
+	}"#;
+
+pub(crate) const ASSET: &str = r#"{
+           "id": "904837e3-3b76-47ec-b432-046db621571b",
+  		   "class": "us_equity",
+  		   "exchange": "NASDAQ",
+  		   "symbol": "AAPL",
+  		   "status": "active",
+  		   "tradable": true,
+  		   "marginable": true,
+  		   "shortable": true,
+  		   "easy_to_borrow": true,
+           "fractionable": true,
+           "name": "Apple Inc. Common Stock",
+           "maintenance_margin_requirement": 30,
+           "attributes": ["has_options", "fractional_eh_enabled"]
+        }"#;
+
+pub(crate) const ORDER: &str = r#"{
+        "id": "904837e3-3b76-47ec-b432-046db621571b",
+	    "client_order_id": "904837e3-3b76-47ec-b432-046db621571b",
+	    "created_at": "2018-10-05T05:48:59Z",
+	    "updated_at": "2018-10-05T05:48:59Z",
+	    "submitted_at": "2018-10-05T05:48:59Z",
+	    "filled_at": "2018-10-05T05:48:59Z",
+	    "expired_at": "2018-10-05T05:48:59Z",
+	    "canceled_at": "2018-10-05T05:48:59Z",
+	    "failed_at": "2018-10-05T05:48:59Z",
+	    "replaced_at": "2018-10-05T05:48:59Z",
+	    "replaced_by": "904837e3-3b76-47ec-b432-046db621571b",
+	    "replaces": null,
+	    "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+	    "symbol": "AAPL",
+	    "asset_class": "us_equity",
+	    "qty": "15",
+	    "filled_qty": "0",
+	    "type": "market",
+	    "side": "buy",
+	    "time_in_force": "day",
+	    "limit_price": "107.00",
+	    "stop_price": "106.00",
+	    "filled_avg_price": "106.00",
+	    "status": "accepted",
+	    "extended_hours": false,
+	    "legs": null,
+        "trail_price": "1.05",
+        "trail_percent": null,
+        "hwm": "108.05"
+    }"#;
+
+pub(crate) const POSITION: &str = r#"{
+	  "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+	  "symbol": "AAPL",
+	  "exchange": "NASDAQ",
+	  "asset_class": "us_equity",
+	  "avg_entry_price": "100.0",
+	  "qty": "5",
+	  "side": "long",
+	  "market_value": "600.0",
+	  "cost_basis": "500.0",
+	  "unrealized_pl": "100.0",
+	  "unrealized_plpc": "0.20",
+	  "unrealized_intraday_pl": "10.0",
+	  "unrealized_intraday_plpc": "0.0084",
+	  "current_price": "120.0",
+	  "lastday_price": "119.0",
+	  "change_today": "0.0084"
+	}"#;
+
+pub(crate) const CALENDAR: &str = r#"[
+        {"date": "2021-11-24", "open": "09:30", "close": "16:00", "session_open": "0400", "session_close": "2000"},
+        {"date": "2021-11-26", "open": "09:30", "close": "13:00", "session_open": "0400", "session_close": "1700"},
+        {"date": "2021-11-29", "open": "09:30", "close": "16:00", "session_open": "0400", "session_close": "2000"}
+    ]"#;
+
+pub(crate) const TRADE_ACTIVITY: &str = r#"{
+  		"activity_type": "FILL",
+  		"cum_qty": "1",
+  		"id": "20190524113406977::8efc7b9a-8b2b-4000-9955-d36e7db0df74",
+  		"leaves_qty": "0",
+  		"price": "1.63",
+  		"qty": "1",
+  		"side": "buy",
+  		"symbol": "LPCN",
+  		"transaction_time": "2019-05-24T15:34:06.977Z",
+  		"order_id": "904837e3-3b76-47ec-b432-046db621571b",
+  		"type": "fill"
+	}"#;
+
+pub(crate) const NON_TRADE_ACTIVITY: &str = r#"{
+  		"activity_type": "CSD",
+  		"id": "20190801011955195::5f596936-6f23-4cef-bdf1-3806aae57dbf",
+  		"date": "2019-08-01",
+  		"net_amount": "0"
+	}"#;
+
+/// Deserialize the fixture with some of its fields replaced.
+pub(crate) fn with_fields<T: DeserializeOwned>(fixture: &str, fields: Value) -> T {
+    let mut value: Value = serde_json::from_str(fixture).unwrap();
+    value
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(value).unwrap()
+}
+
+/// A stable id derived from the name, so fixtures built from the same name share it.
+pub(crate) fn uuid(name: &str) -> Uuid {
+    Uuid::from_u128(name.bytes().fold(0, |acc, b| acc << 8 | b as u128))
+}
+
+/// Parse the symbol.
+pub(crate) fn symbol(symbol: &str) -> Symbol {
+    symbol.parse().unwrap()
+}
+
+/// Parse the symbols, keeping their order.
+pub(crate) fn symbols(symbols: &[&str]) -> Vec<Symbol> {
+    symbols.iter().map(|s| s.parse().unwrap()).collect()
+}
+
+/// An active asset with some of its fields replaced.
+pub(crate) fn asset(fields: Value) -> Asset {
+    with_fields(ASSET, fields)
+}
+
+/// The trading calendar of the Thanksgiving week of 2021, with an early close on the 26th.
+pub(crate) fn calendar() -> TradingCalendar {
+    serde_json::from_str::<Vec<Calendar>>(CALENDAR)
+        .unwrap()
+        .into()
+}
+
+/// A position valued at the price, with the cost basis equal to the market value.
+pub(crate) fn position(symbol: &str, qty: i32, price: &str) -> Position {
+    let price: Decimal = price.parse().unwrap();
+    let value = price * Decimal::from(qty);
+    with_fields(
+        POSITION,
+        json!({
+            "asset_id": uuid(symbol),
+            "symbol": symbol,
+            "qty": qty.to_string(),
+            "side": if qty < 0 { "short" } else { "long" },
+            "avg_entry_price": price,
+            "current_price": price,
+            "lastday_price": price,
+            "market_value": value,
+            "cost_basis": value,
+        }),
+    )
+}
+
+/// A complete fill of the order with the given id, which is also used for the activity id.
+pub(crate) fn fill(
+    id: &str,
+    symbol: &str,
+    side: &str,
+    qty: &str,
+    price: &str,
+    time: &str,
+) -> Activity {
+    with_fields(
+        TRADE_ACTIVITY,
+        json!({
+            "id": id,
+            "order_id": uuid(id),
+            "symbol": symbol,
+            "side": side,
+            "qty": qty,
+            "cum_qty": qty,
+            "price": price,
+            "transaction_time": time,
+        }),
+    )
+}
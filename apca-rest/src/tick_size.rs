@@ -0,0 +1,306 @@
+use crate::assets::Asset;
+use crate::orders::{OrderType, Side, SubmitOrder};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The direction to round a value to a valid increment in.
+pub enum Rounding {
+    /// Round towards negative infinity.
+    Down,
+    /// Round towards positive infinity.
+    Up,
+    /// Round to the nearest increment, with midpoints rounded away from zero.
+    Nearest,
+}
+
+impl Rounding {
+    /// The direction that keeps prices on the conservative side of the requested price: buys
+    /// round down so they never pay more, and sells round up so they never receive less.
+    pub fn for_side(side: &Side) -> Self {
+        match side {
+            Side::Buy => Rounding::Down,
+            Side::Sell => Rounding::Up,
+        }
+    }
+
+    /// Round the value to a multiple of the increment. A zero increment leaves the value
+    /// unchanged.
+    pub fn round(self, value: Decimal, increment: Decimal) -> Decimal {
+        if increment.is_zero() {
+            return value;
+        }
+        let steps = value / increment;
+        let steps = match self {
+            Rounding::Down => steps.floor(),
+            Rounding::Up => steps.ceil(),
+            Rounding::Nearest => {
+                steps.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+            }
+        };
+        (steps * increment).normalize()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// The reason a quantity could not be normalized.
+pub enum TickError {
+    /// The quantity is less than the smallest quantity that can be ordered once rounded.
+    BelowMinimum {
+        /// The rounded quantity
+        qty: Decimal,
+        /// The smallest quantity that can be ordered
+        min: Decimal,
+    },
+}
+
+impl fmt::Display for TickError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TickError::BelowMinimum { qty, min } => {
+                write!(f, "quantity {} is below the minimum of {}", qty, min)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TickError {}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Rounds prices and quantities to the increments an asset can be traded in, so that orders are
+/// not rejected for sub-penny prices or invalid crypto increments.
+///
+/// Prices use the asset's `price_increment` if it has one. Otherwise they follow the sub-penny
+/// rule: $0.01 for prices of $1 and above, and $0.0001 below. Quantities use the asset's
+/// `min_trade_increment` if it has one, and otherwise whole shares, or nine decimal places for
+/// fractionable assets.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     assets::{Asset, GetAsset},
+///     orders::{OrderType, Side, SubmitOrder},
+///     paper_client,
+///     tick_size::TickSize,
+///     Symbol,
+/// };
+/// use rust_decimal::Decimal;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let asset: Asset = client.send(&GetAsset::new("AAPL".parse::<Symbol>()?)).await?;
+///     let order = SubmitOrder::new(asset.symbol.parse()?)
+///         .side(Side::Buy)
+///         .order_type(OrderType::limit(Decimal::new(1501234, 4)));
+///     client.send(&TickSize::new(&asset).normalize_order(order)).await?;
+///     Ok(())
+/// }
+/// ```
+pub struct TickSize {
+    price_increment: Option<Decimal>,
+    qty_increment: Decimal,
+    min_order_size: Option<Decimal>,
+}
+
+impl TickSize {
+    /// Create a normalizer for the asset.
+    pub fn new(asset: &Asset) -> Self {
+        // A zero increment is treated as if the asset had none.
+        let nonzero = |increment: &Decimal| !increment.is_zero();
+        let qty_increment = match asset.min_trade_increment.filter(nonzero) {
+            Some(increment) => increment,
+            None if asset.fractionable => Decimal::new(1, 9),
+            None => Decimal::ONE,
+        };
+        Self {
+            price_increment: asset.price_increment.filter(nonzero),
+            qty_increment,
+            min_order_size: asset.min_order_size,
+        }
+    }
+
+    /// The increment prices around the given price can be quoted in.
+    pub fn price_increment(&self, price: Decimal) -> Decimal {
+        match self.price_increment {
+            Some(increment) => increment,
+            None if price.abs() >= Decimal::ONE => Decimal::new(1, 2),
+            None => Decimal::new(1, 4),
+        }
+    }
+
+    /// The increment quantities can be ordered in.
+    pub fn qty_increment(&self) -> Decimal {
+        self.qty_increment
+    }
+
+    /// Round the price to a valid increment.
+    pub fn round_price(&self, price: Decimal, rounding: Rounding) -> Decimal {
+        rounding.round(price, self.price_increment(price))
+    }
+
+    /// Round the quantity to a valid increment, failing if the result is below the smallest
+    /// quantity that can be ordered.
+    pub fn round_qty(&self, qty: Decimal, rounding: Rounding) -> Result<Decimal, TickError> {
+        let rounded = rounding.round(qty, self.qty_increment);
+        let min = self.min_order_size.unwrap_or(self.qty_increment);
+        if rounded < min {
+            return Err(TickError::BelowMinimum { qty: rounded, min });
+        }
+        Ok(rounded)
+    }
+
+    /// Round the prices of the order type in the given direction. Trailing percentages are not
+    /// changed.
+    pub fn normalize(&self, order_type: &OrderType, rounding: Rounding) -> OrderType {
+        let round = |price: Decimal| self.round_price(price, rounding);
+        match order_type {
+            OrderType::Market => OrderType::Market,
+            OrderType::Limit { limit_price } => OrderType::Limit {
+                limit_price: round(*limit_price),
+            },
+            OrderType::Stop { stop_price } => OrderType::Stop {
+                stop_price: round(*stop_price),
+            },
+            OrderType::StopLimit {
+                limit_price,
+                stop_price,
+            } => OrderType::StopLimit {
+                limit_price: round(*limit_price),
+                stop_price: round(*stop_price),
+            },
+            OrderType::TrailingStop {
+                trail_price,
+                trail_percent,
+            } => OrderType::TrailingStop {
+                trail_price: trail_price.map(round),
+                trail_percent: *trail_percent,
+            },
+        }
+    }
+
+    /// Round the prices of the order type in the direction for the side.
+    pub fn normalize_for_side(&self, order_type: &OrderType, side: &Side) -> OrderType {
+        self.normalize(order_type, Rounding::for_side(side))
+    }
+
+    /// Round the prices of the order in the direction for its side, and its notional value down
+    /// to the cent. The legs of advanced orders are not changed.
+    ///
+    /// The quantity is not changed either: orders only take whole quantities, which are already
+    /// multiples of the increment for all but the rare assets traded in lots. Use `round_qty`
+    /// to check a quantity against the increment and minimum order size before ordering it.
+    pub fn normalize_order(&self, mut order: SubmitOrder) -> SubmitOrder {
+        order.order_type = self.normalize_for_side(&order.order_type, &order.side);
+        order.notional = order
+            .notional
+            .map(|notional| Rounding::Down.round(notional, Decimal::new(1, 2)));
+        order
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_fixtures::asset;
+    use serde_json::json;
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn sub_penny_prices() {
+        let ticks = TickSize::new(&asset(json!({"fractionable": false})));
+        assert_eq!(
+            ticks.round_price(d("150.1234"), Rounding::Down),
+            d("150.12")
+        );
+        assert_eq!(ticks.round_price(d("150.1234"), Rounding::Up), d("150.13"));
+        assert_eq!(
+            ticks.round_price(d("150.125"), Rounding::Nearest),
+            d("150.13")
+        );
+        assert_eq!(
+            ticks.round_price(d("0.123456"), Rounding::Down),
+            d("0.1234")
+        );
+        assert_eq!(ticks.round_price(d("0.99995"), Rounding::Up), d("1"));
+        assert_eq!(ticks.round_price(d("1.00005"), Rounding::Down), d("1"));
+        assert_eq!(ticks.round_price(d("1.00005"), Rounding::Up), d("1.01"));
+        assert_eq!(ticks.round_price(d("12.34"), Rounding::Up), d("12.34"));
+    }
+
+    #[test]
+    fn quantities() {
+        let whole = TickSize::new(&asset(json!({"fractionable": false})));
+        assert_eq!(whole.round_qty(d("2.7"), Rounding::Down), Ok(d("2")));
+        assert_eq!(
+            whole.round_qty(d("0.5"), Rounding::Down),
+            Err(TickError::BelowMinimum {
+                qty: d("0"),
+                min: d("1")
+            })
+        );
+        let fractional = TickSize::new(&asset(json!({"fractionable": true})));
+        assert_eq!(
+            fractional.round_qty(d("0.1234567891"), Rounding::Down),
+            Ok(d("0.123456789"))
+        );
+
+        let crypto = TickSize::new(&asset(json!({
+            "class": "crypto",
+            "symbol": "BTC/USD",
+            "fractionable": true,
+            "min_order_size": "0.001",
+            "min_trade_increment": "0.0001",
+            "price_increment": "1"
+        })));
+        assert_eq!(
+            crypto.round_qty(d("0.01239"), Rounding::Down),
+            Ok(d("0.0123"))
+        );
+        assert!(crypto.round_qty(d("0.00089"), Rounding::Up).is_err());
+        assert_eq!(crypto.round_price(d("43210.5"), Rounding::Up), d("43211"));
+        assert_eq!(crypto.round_price(d("0.5"), Rounding::Down), d("0"));
+    }
+
+    #[test]
+    fn zero_increments() {
+        let ticks = TickSize::new(&asset(json!({
+            "fractionable": false,
+            "min_trade_increment": "0",
+            "price_increment": "0"
+        })));
+        assert_eq!(ticks.qty_increment(), d("1"));
+        assert_eq!(ticks.round_price(d("150.123"), Rounding::Up), d("150.13"));
+        assert_eq!(Rounding::Nearest.round(d("1.5"), d("0")), d("1.5"));
+    }
+
+    #[test]
+    fn orders() {
+        let ticks = TickSize::new(&asset(json!({"fractionable": false})));
+        let stop_limit = OrderType::stop_limit(d("10.005"), d("10.015"));
+        assert_eq!(
+            ticks.normalize_for_side(&stop_limit, &Side::Buy),
+            OrderType::stop_limit(d("10"), d("10.01"))
+        );
+        assert_eq!(
+            ticks.normalize_for_side(&stop_limit, &Side::Sell),
+            OrderType::stop_limit(d("10.01"), d("10.02"))
+        );
+        assert_eq!(
+            ticks.normalize(&OrderType::trail_percent(d("1.234")), Rounding::Down),
+            OrderType::trail_percent(d("1.234"))
+        );
+
+        let order = ticks.normalize_order(
+            SubmitOrder::new("AAPL".parse().unwrap())
+                .side(Side::Sell)
+                .notional(d("100.019"))
+                .order_type(OrderType::limit(d("150.001"))),
+        );
+        assert_eq!(order.order_type, OrderType::limit(d("150.01")));
+        assert_eq!(order.notional, Some(d("100.01")));
+    }
+}
@@ -37,6 +37,18 @@ impl<'de> Visitor<'de> for DateTimeFromEpochSecondsVisitor {
     }
 }
 
+/// Percent-encode everything but the unreserved characters, for use in a URL query or path.
+pub(crate) fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
 pub(crate) fn hm_from_str<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
 where
     D: Deserializer<'de>,
@@ -52,6 +64,33 @@ where
     serializer.collect_str(&value.format("%H:%M").to_string())
 }
 
+/// Parse an optional time in either `HHMM` or `HH:MM` format.
+pub(crate) fn hhmm_from_str_optional<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    s.map(|s| {
+        NaiveTime::parse_from_str(&s, "%H%M")
+            .or_else(|_| NaiveTime::parse_from_str(&s, "%H:%M"))
+            .map_err(de::Error::custom)
+    })
+    .transpose()
+}
+
+pub(crate) fn hhmm_to_string_optional<S>(
+    value: &Option<NaiveTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(v) => serializer.collect_str(&v.format("%H%M").to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
 pub(crate) fn from_str<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
     T: FromStr,
@@ -81,18 +120,23 @@ where
     }
 }
 
+/// Parse an optional value given either as a string or as a JSON number. Any other JSON value is
+/// rejected rather than read as `None`.
 pub(crate) fn from_str_optional<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     T: FromStr,
     T::Err: fmt::Display,
-    D: serde::Deserializer<'de>,
+    D: Deserializer<'de>,
 {
-    let deser_res: Result<Value, _> = serde::Deserialize::deserialize(deserializer);
-    match deser_res {
-        Ok(Value::String(s)) => T::from_str(&s)
-            .map_err(serde::de::Error::custom)
-            .map(Option::from),
-        Ok(_) => Ok(None),
-        Err(_) => Ok(None),
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(s) => T::from_str(&s).map(Some).map_err(de::Error::custom),
+        Value::Number(n) => T::from_str(&n.to_string())
+            .map(Some)
+            .map_err(de::Error::custom),
+        other => Err(de::Error::custom(format!(
+            "expected a string or a number, got {}",
+            other
+        ))),
     }
 }
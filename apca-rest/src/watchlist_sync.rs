@@ -0,0 +1,557 @@
+use crate::assets::{AssetFilter, AssetUniverse, Status};
+use crate::watchlists::{
+    AddAssetToWatchlist, CreateWatchlist, DeleteWatchlist, GetWatchlist, GetWatchlists,
+    RemoveAssetFromWatchlist, UpdateWatchlist, Watchlist,
+};
+use crate::Symbol;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+use vila::{Client, EmptyResponse};
+
+#[derive(Clone, Debug, PartialEq)]
+/// A change to make to the account's watchlists.
+pub enum SyncAction {
+    /// Create a watchlist with the symbols.
+    Create {
+        /// The name of the watchlist
+        name: String,
+        /// The symbols, in order
+        symbols: Vec<Symbol>,
+    },
+    /// Append the symbol to the end of a watchlist.
+    Add {
+        /// The id of the watchlist
+        id: Uuid,
+        /// The name of the watchlist
+        name: String,
+        /// The symbol to add
+        symbol: Symbol,
+    },
+    /// Remove the symbol from a watchlist.
+    Remove {
+        /// The id of the watchlist
+        id: Uuid,
+        /// The name of the watchlist
+        name: String,
+        /// The symbol to remove
+        symbol: Symbol,
+    },
+    /// Replace the content of a watchlist, when its symbols need to be reordered.
+    Replace {
+        /// The id of the watchlist
+        id: Uuid,
+        /// The name of the watchlist
+        name: String,
+        /// The symbols, in order
+        symbols: Vec<Symbol>,
+    },
+    /// Delete a watchlist that is not in the desired state.
+    Delete {
+        /// The id of the watchlist
+        id: Uuid,
+        /// The name of the watchlist
+        name: String,
+    },
+}
+
+impl SyncAction {
+    /// Send the request for the action.
+    pub async fn apply(&self, client: &Client) -> Result<(), vila::Error> {
+        match self {
+            SyncAction::Create { name, symbols } => {
+                let _: Watchlist = client
+                    .send(&CreateWatchlist::new(name, symbols.iter().cloned()))
+                    .await?;
+            }
+            SyncAction::Add { id, symbol, .. } => {
+                let _: Watchlist = client
+                    .send(&AddAssetToWatchlist::new(*id, symbol.clone()))
+                    .await?;
+            }
+            SyncAction::Remove { id, symbol, .. } => {
+                let _: EmptyResponse = client
+                    .send(&RemoveAssetFromWatchlist::new(*id, symbol.clone()))
+                    .await?;
+            }
+            SyncAction::Replace { id, symbols, .. } => {
+                let _: Watchlist = client
+                    .send(&UpdateWatchlist::new(*id).symbols(symbols.iter().cloned()))
+                    .await?;
+            }
+            SyncAction::Delete { id, .. } => {
+                let _: EmptyResponse = client.send(&DeleteWatchlist::new(*id)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SyncAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncAction::Create { name, symbols } => {
+                write!(f, "create {}: {}", name, join(symbols))
+            }
+            SyncAction::Add { name, symbol, .. } => write!(f, "add {} to {}", symbol, name),
+            SyncAction::Remove { name, symbol, .. } => {
+                write!(f, "remove {} from {}", symbol, name)
+            }
+            SyncAction::Replace { name, symbols, .. } => {
+                write!(f, "replace {}: {}", name, join(symbols))
+            }
+            SyncAction::Delete { name, .. } => write!(f, "delete {}", name),
+        }
+    }
+}
+
+fn join(symbols: &[Symbol]) -> String {
+    symbols
+        .iter()
+        .map(Symbol::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// The actions that bring the account's watchlists to the desired state. Its `Display` output
+/// lists one action per line, so it can be reviewed before it is applied.
+pub struct SyncPlan {
+    actions: Vec<SyncAction>,
+}
+
+impl SyncPlan {
+    /// The actions, in the order they are applied.
+    pub fn actions(&self) -> &[SyncAction] {
+        &self.actions
+    }
+
+    /// Whether the watchlists are already in the desired state.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Apply the actions in order, stopping at the first that fails.
+    pub async fn apply(&self, client: &Client) -> Result<(), vila::Error> {
+        for action in &self.actions {
+            action.apply(client).await?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SyncPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+/// Errors that can occur when synchronizing watchlists.
+pub enum SyncError {
+    /// The desired watchlists contain symbols that are not active and tradable in the asset
+    /// universe.
+    UnknownSymbols(Vec<Symbol>),
+    /// Several of the current watchlists share a name, so it is ambiguous which one to change.
+    DuplicateNames(Vec<String>),
+    /// Fetching or updating the watchlists failed.
+    Request(vila::Error),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::UnknownSymbols(symbols) => write!(f, "unknown symbols: {}", join(symbols)),
+            SyncError::DuplicateNames(names) => {
+                write!(f, "duplicate watchlist names: {}", names.join(", "))
+            }
+            SyncError::Request(e) => write!(f, "request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+impl From<vila::Error> for SyncError {
+    fn from(e: vila::Error) -> Self {
+        SyncError::Request(e)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(
+    from = "BTreeMap<String, Vec<Symbol>>",
+    into = "BTreeMap<String, Vec<Symbol>>"
+)]
+/// The desired state of the account's watchlists, as a map from watchlist name to its symbols in
+/// order. It deserializes from that map, so it can be read from a configuration file.
+///
+/// Watchlists are matched to the account's by name. Those that exist are changed by removing and
+/// appending symbols when that gives the desired order, and have their content replaced otherwise.
+/// Watchlists that are not in the desired state are left alone, unless `prune` is set.
+///
+/// # Examples
+/// ```no_run
+/// use apca_rest::{
+///     assets::{AssetUniverse, GetAssets},
+///     paper_client,
+///     watchlist_sync::WatchlistSync,
+///     Symbol,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let client = paper_client("KEY", "SECRET");
+///     let universe = AssetUniverse::fetch(&client, &GetAssets::new()).await?;
+///     let sync = WatchlistSync::new()
+///         .watchlist("Tech", [Symbol::new("AAPL")?, Symbol::new("MSFT")?])
+///         .watchlist("Crypto", [Symbol::new("BTC/USD")?])
+///         .prune(true);
+///     let plan = sync.sync(&client, &universe, true).await?;
+///     print!("{}", plan);
+///     Ok(())
+/// }
+/// ```
+pub struct WatchlistSync {
+    desired: BTreeMap<String, Vec<Symbol>>,
+    prune: bool,
+}
+
+impl From<BTreeMap<String, Vec<Symbol>>> for WatchlistSync {
+    fn from(desired: BTreeMap<String, Vec<Symbol>>) -> Self {
+        desired
+            .into_iter()
+            .fold(Self::new(), |sync, (name, symbols)| {
+                sync.watchlist(name, symbols)
+            })
+    }
+}
+
+impl From<WatchlistSync> for BTreeMap<String, Vec<Symbol>> {
+    fn from(sync: WatchlistSync) -> Self {
+        sync.desired
+    }
+}
+
+impl WatchlistSync {
+    /// Create a desired state with no watchlists.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a watchlist with the symbols in order. Repeated symbols are only kept the first time.
+    pub fn watchlist<T1: ToString, T2: IntoIterator<Item = Symbol>>(
+        mut self,
+        name: T1,
+        symbols: T2,
+    ) -> Self {
+        let mut seen = HashSet::new();
+        let symbols = symbols
+            .into_iter()
+            .filter(|s| seen.insert(s.clone()))
+            .collect();
+        self.desired.insert(name.to_string(), symbols);
+        self
+    }
+
+    /// Delete the account's watchlists that are not in the desired state.
+    pub fn prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// The desired watchlists.
+    pub fn desired(&self) -> &BTreeMap<String, Vec<Symbol>> {
+        &self.desired
+    }
+
+    /// The desired symbols that are not active and tradable in the universe, ordered and without
+    /// repeats.
+    pub fn unknown_symbols(&self, universe: &AssetUniverse) -> Vec<Symbol> {
+        let filter = AssetFilter::new().status(Status::Active).tradable(true);
+        let known: HashSet<&str> = universe
+            .filter(&filter)
+            .map(|a| a.symbol.as_str())
+            .collect();
+        let mut unknown: Vec<Symbol> = self
+            .desired
+            .values()
+            .flatten()
+            .filter(|s| !known.contains(s.as_str()))
+            .cloned()
+            .collect();
+        unknown.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        unknown.dedup();
+        unknown
+    }
+
+    /// Check that every desired symbol is active and tradable in the universe.
+    pub fn validate(&self, universe: &AssetUniverse) -> Result<(), SyncError> {
+        let unknown = self.unknown_symbols(universe);
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(SyncError::UnknownSymbols(unknown))
+        }
+    }
+
+    /// Fetch the account's watchlists with their content. The list of watchlists does not
+    /// include their assets, so each one is fetched by id.
+    pub async fn fetch_current(client: &Client) -> Result<Vec<Watchlist>, vila::Error> {
+        let watchlists: Vec<Watchlist> = client.send(&GetWatchlists).await?;
+        let mut current = Vec::with_capacity(watchlists.len());
+        for watchlist in watchlists {
+            current.push(client.send(&GetWatchlist::new(watchlist.id)).await?);
+        }
+        Ok(current)
+    }
+
+    /// The actions that bring the current watchlists to the desired state. Watchlists are
+    /// deleted first, then created, then changed, each in order of name. Returns an error if
+    /// several current watchlists share a name.
+    pub fn plan(&self, watchlists: &[Watchlist]) -> Result<SyncPlan, SyncError> {
+        let mut current: BTreeMap<&str, &Watchlist> = BTreeMap::new();
+        let mut duplicates = Vec::new();
+        for watchlist in watchlists {
+            if current.insert(watchlist.name.as_str(), watchlist).is_some() {
+                duplicates.push(watchlist.name.clone());
+            }
+        }
+        if !duplicates.is_empty() {
+            duplicates.sort();
+            duplicates.dedup();
+            return Err(SyncError::DuplicateNames(duplicates));
+        }
+        let mut actions = Vec::new();
+        if self.prune {
+            actions.extend(
+                current
+                    .iter()
+                    .filter(|(name, _)| !self.desired.contains_key(**name))
+                    .map(|(name, watchlist)| SyncAction::Delete {
+                        id: watchlist.id,
+                        name: name.to_string(),
+                    }),
+            );
+        }
+        actions.extend(
+            self.desired
+                .iter()
+                .filter(|(name, _)| !current.contains_key(name.as_str()))
+                .map(|(name, symbols)| SyncAction::Create {
+                    name: name.clone(),
+                    symbols: symbols.clone(),
+                }),
+        );
+        for (name, desired) in &self.desired {
+            if let Some(watchlist) = current.get(name.as_str()) {
+                actions.extend(changes(watchlist, desired));
+            }
+        }
+        Ok(SyncPlan { actions })
+    }
+
+    /// Fetch the current watchlists, check the desired symbols against the universe and plan the
+    /// changes. Unless `dry_run` is set, the plan is also applied. The plan is returned either way.
+    pub async fn sync(
+        &self,
+        client: &Client,
+        universe: &AssetUniverse,
+        dry_run: bool,
+    ) -> Result<SyncPlan, SyncError> {
+        self.validate(universe)?;
+        let current = Self::fetch_current(client).await?;
+        let plan = self.plan(&current)?;
+        if !dry_run {
+            plan.apply(client).await?;
+        }
+        Ok(plan)
+    }
+}
+
+fn changes(watchlist: &Watchlist, desired: &[Symbol]) -> Vec<SyncAction> {
+    let current: Vec<Symbol> = watchlist
+        .assets
+        .iter()
+        .map(|a| Symbol::unchecked(&a.symbol))
+        .collect();
+    if current == desired {
+        return Vec::new();
+    }
+    let (kept, removed): (Vec<Symbol>, Vec<Symbol>) =
+        current.iter().cloned().partition(|s| desired.contains(s));
+    let added: Vec<Symbol> = desired
+        .iter()
+        .filter(|s| !current.contains(s))
+        .cloned()
+        .collect();
+    let id = watchlist.id;
+    let name = &watchlist.name;
+    if kept.iter().chain(&added).ne(desired) {
+        return vec![SyncAction::Replace {
+            id,
+            name: name.clone(),
+            symbols: desired.to_vec(),
+        }];
+    }
+    let removals = removed.into_iter().map(|symbol| SyncAction::Remove {
+        id,
+        name: name.clone(),
+        symbol,
+    });
+    let additions = added.into_iter().map(|symbol| SyncAction::Add {
+        id,
+        name: name.clone(),
+        symbol,
+    });
+    removals.chain(additions).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::assets::Asset;
+    use crate::client_with_url;
+    use crate::test_fixtures::{asset, symbols, uuid};
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    const WATCHLIST_ID: &'static str = "fb306e55-16d3-4118-8c3d-c1615fcd4c03";
+    const OTHER_ID: &'static str = "0a3e5f2b-7d0c-4a9e-9a43-3c5c1f0c7e11";
+
+    fn listed(symbol: &str) -> Asset {
+        asset(json!({"symbol": symbol, "id": uuid(symbol)}))
+    }
+
+    fn watchlist(id: &str, name: &str, symbols: &[&str]) -> serde_json::Value {
+        json!({
+            "id": id,
+            "account_id": "1d5493c9-ea39-4377-aa94-340734c368ae",
+            "created_at": "2019-10-30T07:54:42.981322Z",
+            "updated_at": "2019-10-30T07:54:42.981322Z",
+            "name": name,
+            "assets": symbols.iter().map(|s| listed(s)).collect::<Vec<_>>()
+        })
+    }
+
+    fn current(watchlists: &[serde_json::Value]) -> Vec<Watchlist> {
+        serde_json::from_value(json!(watchlists)).unwrap()
+    }
+
+    #[test]
+    fn plan() {
+        let current = current(&[
+            watchlist(WATCHLIST_ID, "Tech", &["AAPL", "TSLA", "MSFT"]),
+            watchlist(OTHER_ID, "Old", &["SPY"]),
+        ]);
+        let sync: WatchlistSync = serde_json::from_value(json!({
+            "Tech": ["AAPL", "MSFT", "NVDA", "AAPL"],
+            "Crypto": ["BTC/USD"]
+        }))
+        .unwrap();
+        assert_eq!(sync.desired()["Tech"], symbols(&["AAPL", "MSFT", "NVDA"]));
+        assert_eq!(
+            sync.plan(&current).unwrap().to_string(),
+            "create Crypto: BTC/USD\nremove TSLA from Tech\nadd NVDA to Tech\n"
+        );
+        assert_eq!(
+            sync.clone().prune(true).plan(&current).unwrap().to_string(),
+            "delete Old\ncreate Crypto: BTC/USD\nremove TSLA from Tech\nadd NVDA to Tech\n"
+        );
+
+        let reordered = WatchlistSync::new().watchlist("Tech", symbols(&["MSFT", "AAPL"]));
+        assert_eq!(
+            reordered.plan(&current).unwrap().actions(),
+            &[SyncAction::Replace {
+                id: WATCHLIST_ID.parse().unwrap(),
+                name: "Tech".into(),
+                symbols: symbols(&["MSFT", "AAPL"]),
+            }]
+        );
+
+        let unchanged = WatchlistSync::new().watchlist("Tech", symbols(&["AAPL", "TSLA", "MSFT"]));
+        assert!(unchanged.plan(&current).unwrap().is_empty());
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let current = current(&[
+            watchlist(WATCHLIST_ID, "Tech", &["AAPL"]),
+            watchlist(OTHER_ID, "Tech", &["MSFT"]),
+        ]);
+        let sync = WatchlistSync::new().watchlist("Tech", symbols(&["AAPL"]));
+        assert!(matches!(
+            sync.plan(&current),
+            Err(SyncError::DuplicateNames(names)) if names == ["Tech"]
+        ));
+    }
+
+    #[test]
+    fn unknown_symbols() {
+        let universe = AssetUniverse::new(vec![
+            listed("AAPL"),
+            listed("MSFT"),
+            asset(json!({"symbol": "GE", "id": uuid("GE"), "status": "inactive"})),
+            asset(json!({"symbol": "TWTR", "id": uuid("TWTR"), "tradable": false})),
+        ]);
+        let sync = WatchlistSync::new()
+            .watchlist("Tech", symbols(&["AAPL", "XYZ", "TWTR"]))
+            .watchlist("More", symbols(&["MSFT", "XYZ", "ABC", "GE"]));
+        assert_eq!(
+            sync.unknown_symbols(&universe),
+            symbols(&["ABC", "GE", "TWTR", "XYZ"])
+        );
+        assert!(matches!(
+            sync.validate(&universe),
+            Err(SyncError::UnknownSymbols(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sync() {
+        let tech = watchlist(WATCHLIST_ID, "Tech", &["AAPL", "TSLA"]);
+        // The list of watchlists does not include their assets.
+        let mut listed_tech = tech.clone();
+        listed_tech.as_object_mut().unwrap().remove("assets");
+        let _list = mock("GET", "/v2/watchlists")
+            .with_body(json!([listed_tech]).to_string())
+            .create();
+        let _get = mock("GET", format!("/v2/watchlists/{}", WATCHLIST_ID).as_str())
+            .with_body(tech.to_string())
+            .create();
+        let remove = mock(
+            "DELETE",
+            format!("/v2/watchlists/{}/TSLA", WATCHLIST_ID).as_str(),
+        )
+        .create();
+        let add = mock("POST", format!("/v2/watchlists/{}", WATCHLIST_ID).as_str())
+            .match_body(Matcher::Json(json!({"symbol": "MSFT"})))
+            .with_body(tech.to_string())
+            .create();
+        let create = mock("POST", "/v2/watchlists")
+            .match_body(Matcher::Json(
+                json!({"name": "Crypto", "symbols": ["BTC/USD"]}),
+            ))
+            .with_body(watchlist(OTHER_ID, "Crypto", &["BTC/USD"]).to_string())
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let universe = AssetUniverse::new(vec![listed("AAPL"), listed("MSFT"), listed("BTC/USD")]);
+        let sync = WatchlistSync::new()
+            .watchlist("Tech", symbols(&["AAPL", "MSFT"]))
+            .watchlist("Crypto", symbols(&["BTC/USD"]));
+        let plan = sync.sync(&client, &universe, true).await.unwrap();
+        assert_eq!(plan.actions().len(), 3);
+        assert!(!remove.matched());
+
+        sync.sync(&client, &universe, false).await.unwrap();
+        remove.assert();
+        add.assert();
+        create.assert();
+    }
+}
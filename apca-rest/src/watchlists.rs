@@ -1,4 +1,6 @@
 use crate::assets::Asset;
+use crate::utils::url_encode;
+use crate::Symbol;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -23,6 +25,47 @@ pub struct Watchlist {
     pub assets: Vec<Asset>,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// Identifies a watchlist by its id, or by its user-defined name.
+pub enum WatchlistIdentifier {
+    /// Watchlist id
+    Id(Uuid),
+    /// Watchlist name
+    Name(String),
+}
+
+impl WatchlistIdentifier {
+    fn path(&self, symbol: Option<&Symbol>) -> String {
+        let symbol = symbol
+            .map(|s| format!("/{}", s.url_encoded()))
+            .unwrap_or_default();
+        match self {
+            WatchlistIdentifier::Id(id) => format!("/v2/watchlists/{}{}", id, symbol),
+            WatchlistIdentifier::Name(name) => {
+                format!("/v2/watchlists:by_name{}?name={}", symbol, url_encode(name))
+            }
+        }
+    }
+}
+
+impl From<Uuid> for WatchlistIdentifier {
+    fn from(id: Uuid) -> Self {
+        WatchlistIdentifier::Id(id)
+    }
+}
+
+impl From<String> for WatchlistIdentifier {
+    fn from(name: String) -> Self {
+        WatchlistIdentifier::Name(name)
+    }
+}
+
+impl<'a> From<&'a str> for WatchlistIdentifier {
+    fn from(name: &'a str) -> Self {
+        WatchlistIdentifier::Name(name.to_string())
+    }
+}
+
 #[derive(Clone, Debug)]
 /// Returns the list of watchlists registered under the account.
 ///
@@ -51,7 +94,7 @@ impl Request for GetWatchlists {
 }
 
 #[derive(Clone, Debug)]
-/// Returns a watchlist identified by the ID.
+/// Returns a watchlist identified by the ID or name.
 ///
 /// # Examples
 /// ```no_run
@@ -65,17 +108,20 @@ impl Request for GetWatchlists {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let id = Uuid::nil();
 ///     let watchlist: Watchlist = client.send(&GetWatchlist::new(id)).await?;
+///     let watchlist: Watchlist = client.send(&GetWatchlist::new("Monday list")).await?;
 ///     Ok(())
 /// }
 /// ```
 pub struct GetWatchlist {
-    id: Uuid,
+    watchlist: WatchlistIdentifier,
 }
 
 impl GetWatchlist {
     /// Create a new request
-    pub fn new(id: Uuid) -> Self {
-        Self { id }
+    pub fn new<T: Into<WatchlistIdentifier>>(watchlist: T) -> Self {
+        Self {
+            watchlist: watchlist.into(),
+        }
     }
 }
 
@@ -84,7 +130,7 @@ impl Request for GetWatchlist {
     type Response = Watchlist;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/watchlists/{}", self.id).into()
+        self.watchlist.path(None).into()
     }
 }
 
@@ -96,28 +142,28 @@ impl Request for GetWatchlist {
 /// use apca_rest::{
 ///     paper_client,
 ///     watchlists::{CreateWatchlist, Watchlist},
+///     Symbol,
 /// };
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let watchlist: Watchlist = client
-///         .send(&CreateWatchlist::new("List", ["AAPL", "TSLA"]))
+///         .send(&CreateWatchlist::new("List", [Symbol::new("AAPL")?, Symbol::new("TSLA")?]))
 ///         .await?;
 ///     Ok(())
 /// }
 /// ```
 pub struct CreateWatchlist {
     name: String,
-    symbols: Vec<String>,
+    symbols: Vec<Symbol>,
 }
 
 impl CreateWatchlist {
     /// Create a new request
-    pub fn new<T1: IntoIterator<Item = T2>, T2: ToString>(name: T2, symbols: T1) -> Self {
-        let symbols = symbols.into_iter().map(|s| s.to_string()).collect();
+    pub fn new<T1: ToString, T2: IntoIterator<Item = Symbol>>(name: T1, symbols: T2) -> Self {
         Self {
             name: name.to_string(),
-            symbols,
+            symbols: symbols.into_iter().collect(),
         }
     }
 }
@@ -136,7 +182,7 @@ impl Request for CreateWatchlist {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 /// Update the name and/or content of watchlist.
 ///
 /// # Examples
@@ -144,33 +190,34 @@ impl Request for CreateWatchlist {
 /// use apca_rest::{
 ///     paper_client,
 ///     watchlists::{UpdateWatchlist, Watchlist},
+///     Symbol,
 /// };
 /// use uuid::Uuid;
 ///
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let id = Uuid::nil();
 ///     let watchlist: Watchlist = client
-///         .send(&UpdateWatchlist::new(id).name("name").symbols(["AAPL"]))
+///         .send(&UpdateWatchlist::new(id).name("name").symbols([Symbol::new("AAPL")?]))
 ///         .await?;
 ///     Ok(())
 /// }
 /// ```
 pub struct UpdateWatchlist {
     #[serde(skip_serializing)]
-    id: Uuid,
+    watchlist: WatchlistIdentifier,
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    symbols: Option<Vec<String>>,
+    symbols: Option<Vec<Symbol>>,
 }
 
 impl UpdateWatchlist {
     /// Create a new request
-    pub fn new(id: Uuid) -> Self {
+    pub fn new<T: Into<WatchlistIdentifier>>(watchlist: T) -> Self {
         Self {
-            id,
+            watchlist: watchlist.into(),
             name: None,
             symbols: None,
         }
@@ -183,9 +230,8 @@ impl UpdateWatchlist {
     }
 
     /// Specify the new symbols in the watchlist
-    pub fn symbols<T1: IntoIterator<Item = T2>, T2: ToString>(mut self, symbols: T1) -> Self {
-        let symbols = symbols.into_iter().map(|x| x.to_string()).collect();
-        self.symbols = Some(symbols);
+    pub fn symbols<T: IntoIterator<Item = Symbol>>(mut self, symbols: T) -> Self {
+        self.symbols = Some(symbols.into_iter().collect());
         self
     }
 }
@@ -196,7 +242,7 @@ impl Request for UpdateWatchlist {
     const METHOD: Method = Method::PUT;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/watchlists/{}", self.id).into()
+        self.watchlist.path(None).into()
     }
 
     fn data(&self) -> RequestData<&Self::Data> {
@@ -204,7 +250,7 @@ impl Request for UpdateWatchlist {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Clone, Debug)]
 /// Append an asset for the symbol to the end of watchlist asset list
 ///
 /// # Examples
@@ -216,25 +262,27 @@ impl Request for UpdateWatchlist {
 /// use uuid::Uuid;
 ///
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let id = Uuid::nil();
-///     let watchlist: Watchlist = client.send(&AddAssetToWatchlist::new(id, "AAPL")).await?;
+///     let watchlist: Watchlist = client
+///         .send(&AddAssetToWatchlist::new(id, "AAPL".parse()?))
+///         .await?;
 ///     Ok(())
 /// }
 /// ```
 pub struct AddAssetToWatchlist {
     #[serde(skip_serializing)]
-    id: Uuid,
-    symbol: String,
+    watchlist: WatchlistIdentifier,
+    symbol: Symbol,
 }
 
 impl AddAssetToWatchlist {
     /// Create a new request
-    pub fn new<T: ToString>(id: Uuid, symbol: T) -> Self {
+    pub fn new<T: Into<WatchlistIdentifier>>(watchlist: T, symbol: Symbol) -> Self {
         Self {
-            id,
-            symbol: symbol.to_string(),
+            watchlist: watchlist.into(),
+            symbol,
         }
     }
 }
@@ -245,7 +293,7 @@ impl Request for AddAssetToWatchlist {
     const METHOD: Method = Method::POST;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/watchlists/{}", self.id).into()
+        self.watchlist.path(None).into()
     }
 
     fn data(&self) -> RequestData<&Self::Data> {
@@ -274,13 +322,15 @@ impl Request for AddAssetToWatchlist {
 /// }
 /// ```
 pub struct DeleteWatchlist {
-    id: Uuid,
+    watchlist: WatchlistIdentifier,
 }
 
 impl DeleteWatchlist {
     /// Create a new request
-    pub fn new(id: Uuid) -> Self {
-        Self { id }
+    pub fn new<T: Into<WatchlistIdentifier>>(watchlist: T) -> Self {
+        Self {
+            watchlist: watchlist.into(),
+        }
     }
 }
 
@@ -290,7 +340,7 @@ impl Request for DeleteWatchlist {
     const METHOD: Method = Method::DELETE;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/watchlists/{}", self.id).into()
+        self.watchlist.path(None).into()
     }
 }
 
@@ -307,26 +357,26 @@ impl Request for DeleteWatchlist {
 /// use vila::EmptyResponse;
 ///
 /// #[tokio::main]
-/// async fn main() -> Result<(), vila::Error> {
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
 ///     let client = paper_client("KEY", "SECRET");
 ///     let id = Uuid::nil();
 ///     let _: EmptyResponse = client
-///         .send(&RemoveAssetFromWatchlist::new(id, "AAPL"))
+///         .send(&RemoveAssetFromWatchlist::new(id, "AAPL".parse()?))
 ///         .await?;
 ///     Ok(())
 /// }
 /// ```
 pub struct RemoveAssetFromWatchlist {
-    id: Uuid,
-    symbol: String,
+    watchlist: WatchlistIdentifier,
+    symbol: Symbol,
 }
 
 impl RemoveAssetFromWatchlist {
     /// Create a new request
-    pub fn new<T: ToString>(id: Uuid, symbol: T) -> Self {
+    pub fn new<T: Into<WatchlistIdentifier>>(watchlist: T, symbol: Symbol) -> Self {
         Self {
-            id,
-            symbol: symbol.to_string(),
+            watchlist: watchlist.into(),
+            symbol,
         }
     }
 }
@@ -337,7 +387,7 @@ impl Request for RemoveAssetFromWatchlist {
     const METHOD: Method = Method::DELETE;
 
     fn endpoint(&self) -> Cow<str> {
-        format!("/v2/watchlists/{}/{}", self.id, self.symbol).into()
+        self.watchlist.path(Some(&self.symbol)).into()
     }
 }
 
@@ -345,7 +395,8 @@ impl Request for RemoveAssetFromWatchlist {
 mod test {
     use super::*;
     use crate::client_with_url;
-    use mockito::mock;
+    use crate::test_fixtures::symbols;
+    use mockito::{mock, Matcher};
 
     #[tokio::test]
     async fn get_watchlists() {
@@ -388,7 +439,7 @@ mod test {
         let url = mockito::server_url();
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
-        let req = CreateWatchlist::new("Monday list", ["SPY", "AMZN"]);
+        let req = CreateWatchlist::new("Monday list", symbols(&["SPY", "AMZN"]));
         client.send(&req).await.unwrap();
     }
 
@@ -406,7 +457,7 @@ mod test {
         let req =
             UpdateWatchlist::new(Uuid::parse_str("1d5493c9-ea39-4377-aa94-340734c368ae").unwrap())
                 .name("Monday list")
-                .symbols(["SPY", "AMZN"]);
+                .symbols(symbols(&["SPY", "AMZN"]));
         client.send(&req).await.unwrap();
     }
 
@@ -425,8 +476,10 @@ mod test {
         let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
 
         let req = AddAssetToWatchlist {
-            id: Uuid::parse_str("1d5493c9-ea39-4377-aa94-340734c368ae").unwrap(),
-            symbol: "SPY".into(),
+            watchlist: Uuid::parse_str("1d5493c9-ea39-4377-aa94-340734c368ae")
+                .unwrap()
+                .into(),
+            symbol: "SPY".parse().unwrap(),
         };
         client.send(&req).await.unwrap();
     }
@@ -445,11 +498,53 @@ mod test {
 
         let req = RemoveAssetFromWatchlist::new(
             Uuid::parse_str("1d5493c9-ea39-4377-aa94-340734c368ae").unwrap(),
-            "FB",
+            "FB".parse().unwrap(),
         );
         client.send(&req).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn get_watchlist_by_name() {
+        let _m = mock("GET", "/v2/watchlists:by_name")
+            .match_query(Matcher::UrlEncoded("name".into(), "Monday list".into()))
+            .with_body(WATCHLIST)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let watchlist = client
+            .send(&GetWatchlist::new("Monday list"))
+            .await
+            .unwrap();
+        assert_eq!(watchlist.name, "Monday List");
+    }
+
+    #[tokio::test]
+    async fn update_watchlist_by_name() {
+        let _m = mock("PUT", "/v2/watchlists:by_name")
+            .match_query(Matcher::UrlEncoded("name".into(), "Monday list".into()))
+            .match_body(r#"{"name":"Tuesday list"}"#)
+            .with_body(WATCHLIST)
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let req = UpdateWatchlist::new("Monday list").name("Tuesday list");
+        client.send(&req).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn remove_asset_from_watchlist_by_name() {
+        let _m = mock("DELETE", "/v2/watchlists:by_name/BTC%2FUSD")
+            .match_query(Matcher::UrlEncoded("name".into(), "Crypto & more".into()))
+            .create();
+        let url = mockito::server_url();
+        let client = client_with_url(&url, "APCA_API_KEY_ID", "APCA_API_SECRET_KEY");
+
+        let req = RemoveAssetFromWatchlist::new("Crypto & more", "BTC/USD".parse().unwrap());
+        client.send(&req).await.unwrap();
+    }
+
     #[tokio::test]
     async fn delete_watchlist() {
         let _m = mock(